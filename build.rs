@@ -0,0 +1,31 @@
+extern crate pkg_config;
+
+use std::env;
+
+// sigrok-sys links libsigrok through the "libsigrok" pkg-config package. For
+// installs that aren't found under that name, these add link directives of
+// this crate's own:
+//   SIGROK_LIB_DIR          directory holding the library, without pkg-config
+//   SIGROK_LIB_NAME         library to link, e.g. "sigrok-custom" (default "sigrok")
+//   SIGROK_PKG_CONFIG_NAME  pkg-config package to probe instead of "libsigrok"
+// The first two take precedence. With none set nothing is emitted, and linking
+// is left to sigrok-sys as before.
+fn main() {
+    for var in &["SIGROK_LIB_DIR", "SIGROK_LIB_NAME", "SIGROK_PKG_CONFIG_NAME"] {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+
+    let dir = env::var("SIGROK_LIB_DIR").ok();
+    let name = env::var("SIGROK_LIB_NAME").ok();
+    if dir.is_some() || name.is_some() {
+        if let Some(dir) = dir {
+            println!("cargo:rustc-link-search=native={}", dir);
+        }
+        println!("cargo:rustc-link-lib={}", name.unwrap_or_else(|| "sigrok".to_owned()));
+    } else if let Ok(package) = env::var("SIGROK_PKG_CONFIG_NAME") {
+        // Prints the search paths and libraries itself.
+        if let Err(err) = pkg_config::probe_library(&package) {
+            panic!("Could not find {:?} through pkg-config: {}", package, err);
+        }
+    }
+}
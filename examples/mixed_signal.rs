@@ -0,0 +1,30 @@
+extern crate sigrok;
+
+use sigrok::testing;
+
+// Captures logic and analog data side by side from the demo device and prints
+// the first few samples of each on a shared timeline. Needs the `testing`
+// feature.
+fn main() {
+    let options = testing::DemoOptions {
+        pattern: Some("sigrok".to_owned()),
+        ..Default::default()
+    };
+    let mut demo = match testing::mixed_signal_demo(&options) {
+        Ok(demo) => demo,
+        Err(err) => {
+            println!("No demo device: {}", err);
+            return;
+        }
+    };
+
+    let capture = demo.device.capture_mixed(&mut demo.session, 1000).unwrap();
+    println!("{} samples at {} Hz", capture.len(), capture.samplerate());
+    for n in 0..capture.len().min(10) {
+        let logic = capture.logic().sample(n).unwrap_or(&[]);
+        let analog: Vec<String> = capture.analog().iter()
+            .map(|&(ref name, ref values)| format!("{}={:.3}", name, values[n]))
+            .collect();
+        println!("{:?} {:02x?} {}", capture.time_of_sample(n as u64), logic, analog.join(" "));
+    }
+}
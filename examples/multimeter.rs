@@ -0,0 +1,78 @@
+extern crate sigrok;
+
+use sigrok::{Sigrok, Session, DriverInstance, Datafeed, Function, ConfigOption, Mq, MqFlag};
+
+// Reads measurements from the first multimeter found. Without one, the demo
+// driver's analog channels are used instead.
+fn main() {
+    let mut ctx = Sigrok::new().unwrap();
+    let mut ses = Session::new(&mut ctx).unwrap();
+
+    let device = match find_multimeter(&ctx).or_else(|| find_demo(&ctx)) {
+        Some(device) => device,
+        None => {
+            println!("No multimeter or demo device found.");
+            return;
+        }
+    };
+
+    println!("Using {} {}", device.vendor().unwrap_or_default(), device.model().unwrap_or_default());
+
+    // Not every meter lets the measured quantity be chosen remotely.
+    let dc_volts = ConfigOption::MeasuredQuantity(Mq::Voltage, vec![MqFlag::Dc]);
+    if let Err(err) = device.config_set_checked(&dc_volts) {
+        println!("Keeping current measurement: {}", err);
+    }
+
+    ses.add_instance(&device);
+    ses.callback_add(Box::new(on_data));
+    ses.start();
+    sigrok::main_loop();
+}
+
+fn find_multimeter(ctx: &Sigrok) -> Option<DriverInstance> {
+    for driver in ctx.drivers() {
+        if let Some(driver) = ctx.init_driver(&driver) {
+            if driver.functions().contains(&Function::Multimeter) {
+                if let Some(device) = driver.scan().into_iter().next() {
+                    return Some(device);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn find_demo(ctx: &Sigrok) -> Option<DriverInstance> {
+    let driver = ctx.drivers().into_iter().find(|x| x.name() == "demo")?;
+    let device = ctx.init_driver(&driver)?.scan().into_iter().next()?;
+
+    // Only keep the analog channels enabled.
+    for channel in device.channels() {
+        if channel.is_logic() {
+            channel.disable();
+        }
+    }
+    Some(device)
+}
+
+fn on_data(_: &DriverInstance, data: &Datafeed) {
+    match data {
+        &Datafeed::Analog(ref analog) => {
+            let unit = analog.unit.map(|x| x.to_string()).unwrap_or_default();
+            let coupling = if analog.has_flag(MqFlag::Ac) {
+                " AC"
+            } else if analog.has_flag(MqFlag::Dc) {
+                " DC"
+            } else {
+                ""
+            };
+            if let Ok(values) = analog.to_float() {
+                for value in values {
+                    println!("{} {}{}", analog.format_value(value), unit, coupling);
+                }
+            }
+        }
+        _ => { }
+    }
+}
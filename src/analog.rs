@@ -0,0 +1,459 @@
+use sigrok_sys::{Struct_sr_datafeed_analog, Struct_sr_channel, Struct_sr_rational, sr_analog_to_float};
+use num_rational::Ratio;
+use glib_sys::GSList;
+use std::fmt;
+use std::io;
+use std::slice;
+use {DriverChannel, Mq, MqFlag, Unit};
+
+pub struct Analog<'a> {
+    context: *const Struct_sr_datafeed_analog,
+    pub data: &'a [u8],
+    pub num_samples: u32,
+    pub unit_size: u8,
+    pub mq: Option<Mq>,
+    pub unit: Option<Unit>,
+    pub mq_flags: u64,
+    // Channels the samples are interleaved across, in packet order. Only enabled
+    // channels appear; see DriverInstance::analog_channel_layout.
+    pub channels: Vec<DriverChannel>,
+    digits: i8,
+}
+
+// Summarizes the packet's metadata instead of dumping its data.
+impl<'a> fmt::Debug for Analog<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Analog")
+            .field("channels", &self.channels.iter().map(|x| x.name()).collect::<Vec<_>>())
+            .field("samples", &self.num_samples)
+            .field("mq", &self.mq)
+            .field("mq_flags", &MqFlag::from_bits(self.mq_flags))
+            .field("unit", &self.unit)
+            .field("digits", &self.digits)
+            .finish()
+    }
+}
+
+// The packet's raw sample encoding, field for field as libsigrok reports it,
+// for consumers that decode `data` themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalogEncoding {
+    pub unit_size: u8,
+    pub is_signed: bool,
+    pub is_float: bool,
+    pub is_bigendian: bool,
+    // Digits after the decimal point, or significant digits if not decimal.
+    pub digits: i8,
+    pub is_digits_decimal: bool,
+    pub scale: Ratio<i64>,
+    pub offset: Ratio<i64>,
+}
+
+// Iterator returned by Analog::iter_values.
+#[derive(Debug, Clone)]
+pub struct AnalogValues<'a> {
+    samples: slice::Chunks<'a, u8>,
+    encoding: AnalogEncoding,
+    scale: f64,
+    offset: f64,
+}
+
+impl<'a> Iterator for AnalogValues<'a> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let raw = decode_sample(self.samples.next()?, &self.encoding);
+        Some((raw * self.scale + self.offset) as f32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+// Summary of a packet's decoded values, across all of its channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalogStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub rms: f32,
+}
+
+impl<'a> Analog<'a> {
+    // Validates the C-reported sizes before building the data slice, so a malformed
+    // packet is rejected instead of producing an out-of-bounds slice.
+    pub(crate) unsafe fn from_raw(analog: *const Struct_sr_datafeed_analog) -> io::Result<Analog<'a>> {
+        if analog.is_null() || (*analog).encoding.is_null() || (*analog).meaning.is_null() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Analog packet is missing its encoding or meaning"));
+        }
+        let encoding = (*analog).encoding;
+        let meaning = (*analog).meaning;
+
+        let mut channels = vec![];
+        let mut gslist: *mut GSList = (*meaning).channels;
+        while !gslist.is_null() {
+            channels.push(DriverChannel {
+                context: (*gslist).data as *mut Struct_sr_channel,
+            });
+            gslist = (*gslist).next;
+        }
+
+        let unit_size = (*encoding).unitsize as u8;
+        let length = match analog_data_len((*analog).num_samples, channels.len(), unit_size) {
+            Some(length) => length,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Analog packet size overflows")),
+        };
+        let data = if length == 0 {
+            &[][..]
+        } else if (*analog).data.is_null() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Analog packet has no data"));
+        } else {
+            slice::from_raw_parts::<u8>((*analog).data as *const u8, length)
+        };
+
+        Ok(Analog {
+            context: analog,
+            data: data,
+            num_samples: (*analog).num_samples as u32,
+            unit_size: unit_size,
+            mq: Mq::from_raw((*meaning).mq as u32),
+            unit: Unit::from_raw((*meaning).unit as u32),
+            mq_flags: (*meaning).mqflags as u64,
+            channels: channels,
+            digits: if (*analog).spec.is_null() {
+                (*encoding).digits
+            } else {
+                (*(*analog).spec).spec_digits
+            },
+        })
+    }
+
+    // Digits after the decimal point the device displays; negative values round
+    // to tens, hundreds, etc. This is the packet's spec digits (the device's own
+    // display precision), falling back to the digits of the encoding. It's the
+    // value that matters for a readout: SR_CONF_DIGITS only selects the meter's
+    // resolution, which is then reflected here.
+    pub fn display_digits(&self) -> i8 {
+        self.digits
+    }
+
+    // Formats a decoded value at the display precision, e.g. "1.502" for 3 digits.
+    pub fn format_value(&self, value: f32) -> String {
+        format_digits(value, self.digits)
+    }
+
+    // Formats a decoded value with an SI prefix chosen by magnitude and the unit,
+    // e.g. "1.50 mV" or "3.30 kΩ". Digits shift with the prefix so precision is
+    // kept. Units that aren't prefixed (°C, %, dB, ...) are left unscaled.
+    pub fn format_si(&self, value: f32) -> String {
+        format_si(value, self.digits, self.unit)
+    }
+
+    pub fn encoding(&self) -> AnalogEncoding {
+        unsafe {
+            let encoding = &*(*self.context).encoding;
+            AnalogEncoding {
+                unit_size: encoding.unitsize as u8,
+                is_signed: encoding.is_signed != 0,
+                is_float: encoding.is_float != 0,
+                is_bigendian: encoding.is_bigendian != 0,
+                digits: encoding.digits,
+                is_digits_decimal: encoding.is_digits_decimal != 0,
+                scale: encoding_ratio(encoding.scale),
+                offset: encoding_ratio(encoding.offset),
+            }
+        }
+    }
+
+    // The encoding's scale and offset: raw values are multiplied by the scale,
+    // then the offset is added. See ToF64 for converting them.
+    pub fn scale(&self) -> Ratio<i64> {
+        self.encoding().scale
+    }
+
+    pub fn offset(&self) -> Ratio<i64> {
+        self.encoding().offset
+    }
+
+    pub fn has_flag(&self, flag: MqFlag) -> bool {
+        self.mq_flags & flag.to_raw() as u64 != 0
+    }
+
+    pub fn to_float(&self) -> io::Result<Vec<f32>> {
+        let count = (self.num_samples as usize).checked_mul(self.channels.len())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Analog packet size overflows"))?;
+        let mut values = vec![0.0f32; count];
+        unsafe {
+            if sr_analog_to_float(self.context, values.as_mut_ptr()) != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Could not convert analog data"));
+            }
+        }
+        Ok(values)
+    }
+
+    // Decoded values multiplied by a probe attenuation factor. Only needed when
+    // the device doesn't know about the probe; drivers supporting ProbeFactor
+    // already report scaled values.
+    pub fn to_float_scaled(&self, probe_factor: u64) -> io::Result<Vec<f32>> {
+        Ok(scale_values(self.to_float()?, probe_factor))
+    }
+
+    // Decodes values one at a time straight from `data`, in the same interleaved
+    // order as to_float and with the encoding's scale and offset applied, without
+    // allocating. Fails up front for unit sizes libsigrok can't decode either.
+    pub fn iter_values(&self) -> io::Result<AnalogValues<'a>> {
+        let encoding = self.encoding();
+        let size = encoding.unit_size as usize;
+        let supported = if encoding.is_float { size == 4 || size == 8 } else { [1, 2, 4, 8].contains(&size) };
+        if !supported {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Unsupported analog encoding: {} byte {}", size, if encoding.is_float { "float" } else { "integer" })));
+        }
+        Ok(AnalogValues {
+            samples: self.data.chunks(size),
+            encoding: encoding,
+            scale: ratio_f64(encoding.scale),
+            offset: ratio_f64(encoding.offset),
+        })
+    }
+
+    // None for a packet without samples. Computed in one pass over the raw
+    // data, see iter_values.
+    pub fn stats(&self) -> io::Result<Option<AnalogStats>> {
+        Ok(value_stats(self.iter_values()?))
+    }
+
+    // Decoded values split per channel, in the same order as `channels`.
+    pub fn to_float_channels(&self) -> io::Result<Vec<Vec<f32>>> {
+        Ok(deinterleave(&self.to_float()?, self.channels.len()))
+    }
+
+    // Appends the packet's decoded values to per-channel buffers keyed by
+    // channel name, adding channels in the order they first appear. Shared by
+    // the collectors (FrameCollector, MixedCapture).
+    pub(crate) fn demux_into(&self, channels: &mut Vec<(String, Vec<f32>)>) -> io::Result<()> {
+        let values = self.to_float_channels()?;
+        for (channel, samples) in self.channels.iter().zip(values) {
+            let name = channel.name();
+            match channels.iter().position(|x| x.0 == name) {
+                Some(i) => channels[i].1.extend(samples),
+                None => channels.push((name, samples)),
+            }
+        }
+        Ok(())
+    }
+
+    // Thresholds each channel's decoded physical values (in the packet's unit):
+    // samples at or above `threshold` are high.
+    pub fn to_logic(&self, threshold: f32) -> io::Result<Vec<Vec<bool>>> {
+        Ok(self.to_float_channels()?
+            .into_iter()
+            .map(|values| threshold_values(&values, threshold))
+            .collect())
+    }
+}
+
+// Multi-channel packets are interleaved sample by sample.
+fn deinterleave<T: Copy>(values: &[T], channels: usize) -> Vec<Vec<T>> {
+    (0..channels)
+        .map(|c| values.iter().skip(c).step_by(channels).cloned().collect())
+        .collect()
+}
+
+// Single pass; sums are kept in f64 so long packets don't lose precision.
+fn value_stats<I: IntoIterator<Item = f32>>(values: I) -> Option<AnalogStats> {
+    let mut values = values.into_iter();
+    let first = values.next()?;
+    let (mut min, mut max) = (first, first);
+    let (mut sum, mut sum_sq) = (first as f64, first as f64 * first as f64);
+    let mut n = 1f64;
+    for x in values {
+        min = min.min(x);
+        max = max.max(x);
+        sum += x as f64;
+        sum_sq += x as f64 * x as f64;
+        n += 1.0;
+    }
+    Some(AnalogStats {
+        min,
+        max,
+        mean: (sum / n) as f32,
+        rms: (sum_sq / n).sqrt() as f32,
+    })
+}
+
+fn format_digits(value: f32, digits: i8) -> String {
+    if digits >= 0 {
+        format!("{:.*}", digits as usize, value)
+    } else {
+        let step = 10f64.powi(-digits as i32);
+        format!("{:.0}", (value as f64 / step).round() * step)
+    }
+}
+
+// libsigrok rationals have an unsigned denominator; ones past i64::MAX are
+// clamped.
+// One sample of `encoding.unit_size` bytes, before scale and offset. Unit sizes
+// are checked by Analog::iter_values.
+fn decode_sample(bytes: &[u8], encoding: &AnalogEncoding) -> f64 {
+    let mut buf = [0u8; 8];
+    let n = bytes.len();
+    let raw = if encoding.is_bigendian {
+        buf[8 - n..].copy_from_slice(bytes);
+        u64::from_be_bytes(buf)
+    } else {
+        buf[..n].copy_from_slice(bytes);
+        u64::from_le_bytes(buf)
+    };
+    if encoding.is_float {
+        if n == 4 { f32::from_bits(raw as u32) as f64 } else { f64::from_bits(raw) }
+    } else if encoding.is_signed {
+        let shift = 64 - 8 * n as u32;
+        ((raw << shift) as i64 >> shift) as f64
+    } else {
+        raw as f64
+    }
+}
+
+fn ratio_f64(value: Ratio<i64>) -> f64 {
+    if *value.denom() == 0 {
+        return 0.0;
+    }
+    *value.numer() as f64 / *value.denom() as f64
+}
+
+fn encoding_ratio(value: Struct_sr_rational) -> Ratio<i64> {
+    Ratio::new_raw(value.p, value.q.min(i64::max_value() as u64) as i64)
+}
+
+const SI_PREFIXES: [(i32, &'static str); 9] = [
+    (-12, "p"), (-9, "n"), (-6, "\u{00B5}"), (-3, "m"), (0, ""), (3, "k"), (6, "M"), (9, "G"), (12, "T"),
+];
+
+fn takes_si_prefix(unit: Unit) -> bool {
+    match unit {
+        Unit::Celsius | Unit::Fahrenheit | Unit::Percentage | Unit::Boolean | Unit::Unitless |
+        Unit::DecibelMw | Unit::DecibelVolt | Unit::DecibelSpl | Unit::Concentration |
+        Unit::RevolutionsPerMinute | Unit::Humidity293k | Unit::Degree | Unit::Carat | Unit::Ounce |
+        Unit::TroyOunce | Unit::Pound | Unit::Pennyweight | Unit::Grain | Unit::Tael | Unit::Momme |
+        Unit::Tola | Unit::Piece => false,
+        _ => true,
+    }
+}
+
+fn format_si(value: f32, digits: i8, unit: Option<Unit>) -> String {
+    let symbol = unit.map(|x| x.to_string()).unwrap_or_default();
+    let mut exponent = 0;
+    if unit.map_or(false, takes_si_prefix) && value != 0.0 && value.is_finite() {
+        exponent = (((value.abs() as f64).log10() / 3.0).floor() as i32 * 3).max(-12).min(12);
+    }
+    let prefix = SI_PREFIXES.iter().find(|x| x.0 == exponent).map_or("", |x| x.1);
+    let scaled = (value as f64 / 10f64.powi(exponent)) as f32;
+    let digits = (digits as i32 + exponent).max(i8::min_value() as i32).min(i8::max_value() as i32) as i8;
+    let number = format_digits(scaled, digits);
+    if symbol.is_empty() && prefix.is_empty() {
+        number
+    } else {
+        format!("{} {}{}", number, prefix, symbol)
+    }
+}
+
+fn scale_values(mut values: Vec<f32>, factor: u64) -> Vec<f32> {
+    for x in values.iter_mut() {
+        *x *= factor as f32;
+    }
+    values
+}
+
+fn threshold_values(values: &[f32], threshold: f32) -> Vec<bool> {
+    values.iter().map(|&x| x >= threshold).collect()
+}
+
+fn analog_data_len(num_samples: u32, channels: usize, unit_size: u8) -> Option<usize> {
+    (num_samples as usize)
+        .checked_mul(channels)
+        .and_then(|x| x.checked_mul(unit_size as usize))
+}
+
+#[test]
+fn it_rejects_oversized_analog_packets() {
+    assert_eq!(analog_data_len(1024, 2, 4), Some(8192));
+    assert_eq!(analog_data_len(0, 2, 4), Some(0));
+    assert_eq!(analog_data_len(u32::max_value(), usize::max_value(), 4), None);
+    assert_eq!(analog_data_len(2, usize::max_value() / 2 + 1, 1), None);
+}
+
+#[test]
+fn it_thresholds_square_wave() {
+    let square = [0.0, 0.1, 3.3, 3.2, 0.0, 3.3, -0.1, 3.4];
+    let channels = deinterleave(&square, 2);
+    assert_eq!(channels[0], vec![0.0, 3.3, 0.0, -0.1]);
+    assert_eq!(threshold_values(&channels[1], 1.65), vec![false, true, true, true]);
+    assert_eq!(threshold_values(&square[..4], 1.65), vec![false, false, true, true]);
+}
+
+#[test]
+fn it_scales_by_probe_factor() {
+    assert_eq!(scale_values(vec![0.5, -0.25], 10), vec![5.0, -2.5]);
+    assert_eq!(scale_values(vec![0.5], 1), vec![0.5]);
+}
+
+#[test]
+fn it_computes_stats() {
+    assert_eq!(value_stats(vec![]), None);
+    let stats = value_stats(vec![1.0, -1.0, 1.0, -1.0]).unwrap();
+    assert_eq!(stats, AnalogStats { min: -1.0, max: 1.0, mean: 0.0, rms: 1.0 });
+    let stats = value_stats(vec![3.0, 4.0]).unwrap();
+    assert_eq!(stats.mean, 3.5);
+    assert!((stats.rms - 12.5f32.sqrt()).abs() < 1e-6);
+}
+
+#[test]
+fn it_formats_display_digits() {
+    assert_eq!(format_digits(1.502, 3), "1.502");
+    assert_eq!(format_digits(1.50200001, 4), "1.5020");
+    assert_eq!(format_digits(-0.25, 1), "-0.2");
+    assert_eq!(format_digits(12.7, 0), "13");
+    assert_eq!(format_digits(1234.0, -2), "1200");
+}
+
+#[test]
+fn it_formats_si_prefixes() {
+    assert_eq!(format_si(0.0015, 5, Some(Unit::Volt)), "1.50 mV");
+    assert_eq!(format_si(3300.0, -1, Some(Unit::Ohm)), "3.30 k\u{03A9}");
+    assert_eq!(format_si(1.5, 3, Some(Unit::Volt)), "1.500 V");
+    assert_eq!(format_si(0.0, 3, Some(Unit::Volt)), "0.000 V");
+    assert_eq!(format_si(0.0025, 6, Some(Unit::Celsius)), "0.002500 \u{00B0}C");
+    assert_eq!(format_si(2500.0, 0, Some(Unit::Percentage)), "2500 %");
+    assert_eq!(format_si(42.0, 0, None), "42");
+}
+
+#[test]
+fn it_decodes_raw_samples() {
+    let encoding = |unit_size, is_signed, is_float, is_bigendian| AnalogEncoding {
+        unit_size: unit_size,
+        is_signed: is_signed,
+        is_float: is_float,
+        is_bigendian: is_bigendian,
+        digits: 0,
+        is_digits_decimal: true,
+        scale: Ratio::new(1, 1),
+        offset: Ratio::new(0, 1),
+    };
+    assert_eq!(decode_sample(&1.5f32.to_le_bytes(), &encoding(4, true, true, false)), 1.5);
+    assert_eq!(decode_sample(&(-2.25f64).to_be_bytes(), &encoding(8, true, true, true)), -2.25);
+    assert_eq!(decode_sample(&[0xff], &encoding(1, true, false, false)), -1.0);
+    assert_eq!(decode_sample(&[0xff], &encoding(1, false, false, false)), 255.0);
+    assert_eq!(decode_sample(&[0xfe, 0xff], &encoding(2, true, false, false)), -2.0);
+    assert_eq!(decode_sample(&[0x01, 0x00], &encoding(2, false, false, true)), 256.0);
+
+    let data = [0x00, 0x80, 0xff, 0x7f];
+    let values = AnalogValues {
+        samples: data.chunks(2),
+        encoding: encoding(2, true, false, false),
+        scale: 0.5,
+        offset: 1.0,
+    };
+    assert_eq!(values.collect::<Vec<_>>(), vec![-16383.0, 16384.5]);
+}
@@ -0,0 +1,88 @@
+use std::io;
+use std::slice;
+use {Sigrok, Driver, Function};
+
+// A driver's details as listed in a DriverCatalog.
+#[derive(Debug)]
+pub struct DriverEntry {
+    pub name: String,
+    pub long_name: String,
+    pub api_version: i32,
+    driver: Driver,
+}
+
+impl DriverEntry {
+    pub fn driver(&self) -> &Driver {
+        &self.driver
+    }
+}
+
+// The drivers of a context with their names read once, e.g. for a driver
+// selection menu. Functions come from Sigrok::driver_capabilities, so they're
+// only queried (and cached on the context) when first asked for.
+#[derive(Debug)]
+pub struct DriverCatalog<'a> {
+    ctx: &'a Sigrok,
+    entries: Vec<DriverEntry>,
+}
+
+impl<'a> DriverCatalog<'a> {
+    pub fn new(ctx: &'a Sigrok) -> DriverCatalog<'a> {
+        let mut catalog = DriverCatalog {
+            ctx: ctx,
+            entries: vec![],
+        };
+        catalog.rebuild();
+        catalog
+    }
+
+    // Re-reads the driver list, dropping the context's cached functions.
+    pub fn rebuild(&mut self) {
+        *self.ctx.capabilities.borrow_mut() = None;
+        self.entries = self.ctx.drivers().into_iter()
+            .map(|driver| DriverEntry {
+                name: driver.name(),
+                long_name: driver.long_name(),
+                api_version: driver.api_version(),
+                driver: driver,
+            })
+            .collect();
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DriverEntry> {
+        self.entries.iter().find(|x| x.name == name)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, DriverEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Fails with NotFound for an unknown driver, or Other if the driver
+    // couldn't be initialized (driver_capabilities skips it).
+    pub fn functions(&self, name: &str) -> io::Result<Vec<Function>> {
+        if self.get(name).is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("No driver named {:?}", name)));
+        }
+        self.ctx.driver_capabilities().into_iter()
+            .find(|x| x.0 == name)
+            .map(|x| x.1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, format!("Could not initialize driver {:?}", name)))
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b DriverCatalog<'a> {
+    type Item = &'b DriverEntry;
+    type IntoIter = slice::Iter<'b, DriverEntry>;
+
+    fn into_iter(self) -> slice::Iter<'b, DriverEntry> {
+        self.entries.iter()
+    }
+}
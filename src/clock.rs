@@ -0,0 +1,44 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use logic::sample_time;
+use Datafeed;
+
+// Maps sample numbers to wall-clock time, from a capture's absolute start time
+// (as sent in the header) and its samplerate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureClock {
+    pub start: SystemTime,
+    pub samplerate: u64,
+}
+
+impl CaptureClock {
+    // None unless `data` is a header packet with a valid start time.
+    pub fn from_header(data: &Datafeed, samplerate: u64) -> Option<CaptureClock> {
+        match data {
+            &Datafeed::Header { start_time, .. } if start_time.sec >= 0 && start_time.nsec >= 0 => {
+                let since_epoch = Duration::new(start_time.sec as u64, start_time.nsec as u32);
+                UNIX_EPOCH.checked_add(since_epoch).map(|start| CaptureClock {
+                    start,
+                    samplerate,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // With a samplerate of 0 every sample maps to the start time.
+    pub fn time_of_sample(&self, n: u64) -> SystemTime {
+        self.start + sample_time(n, self.samplerate)
+    }
+}
+
+#[test]
+fn it_timestamps_samples() {
+    let clock = CaptureClock {
+        start: UNIX_EPOCH + Duration::from_secs(1_500_000_000),
+        samplerate: 1_000_000,
+    };
+    assert_eq!(clock.time_of_sample(0), clock.start);
+    assert_eq!(clock.time_of_sample(1_500_000), clock.start + Duration::from_millis(1_500));
+    let unknown = CaptureClock { samplerate: 0, ..clock };
+    assert_eq!(unknown.time_of_sample(42), clock.start);
+}
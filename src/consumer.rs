@@ -0,0 +1,72 @@
+use time;
+use {Analog, Datafeed, DriverInstance, Logic};
+
+// A stateful receiver for session packets, e.g. a protocol decoder or a file
+// writer. Every method defaults to doing nothing, so implement only the
+// packets you need. Closures taking (&DriverInstance, &Datafeed) implement it
+// too, receiving every packet through on_datafeed.
+pub trait DatafeedConsumer {
+    fn on_header(&mut self, _device: &DriverInstance, _feed_version: i32, _start_time: time::Timespec) {}
+
+    fn on_logic(&mut self, _device: &DriverInstance, _logic: &Logic) {}
+
+    fn on_analog(&mut self, _device: &DriverInstance, _analog: &Analog) {}
+
+    fn on_trigger(&mut self, _device: &DriverInstance, _sample: Option<u64>) {}
+
+    fn on_end(&mut self, _device: &DriverInstance) {}
+
+    // The run failed before this device's feed ended, e.g. it was unplugged.
+    fn on_aborted(&mut self, _device: &DriverInstance) {}
+
+    // Called for every packet; dispatches to the methods above. Packets without
+    // a dedicated method (frames, unknown types) are ignored.
+    fn on_datafeed(&mut self, device: &DriverInstance, data: &Datafeed) {
+        match data {
+            &Datafeed::Header { feed_version, start_time } => self.on_header(device, feed_version, start_time),
+            &Datafeed::Logic(ref logic) => self.on_logic(device, logic),
+            &Datafeed::Analog(ref analog) => self.on_analog(device, analog),
+            &Datafeed::Trigger { sample } => self.on_trigger(device, sample),
+            &Datafeed::End => self.on_end(device),
+            &Datafeed::Aborted => self.on_aborted(device),
+            _ => {}
+        }
+    }
+}
+
+impl<F: FnMut(&DriverInstance, &Datafeed)> DatafeedConsumer for F {
+    fn on_datafeed(&mut self, device: &DriverInstance, data: &Datafeed) {
+        self(device, data)
+    }
+}
+
+#[test]
+fn it_dispatches_to_consumer_methods() {
+    #[derive(Default)]
+    struct Counter {
+        triggers: Vec<Option<u64>>,
+        ends: usize,
+    }
+
+    impl DatafeedConsumer for Counter {
+        fn on_trigger(&mut self, _: &DriverInstance, sample: Option<u64>) {
+            self.triggers.push(sample);
+        }
+
+        fn on_end(&mut self, _: &DriverInstance) {
+            self.ends += 1;
+        }
+    }
+
+    let device = DriverInstance { context: ::std::ptr::null_mut() };
+    let mut counter = Counter::default();
+    counter.on_datafeed(&device, &Datafeed::Trigger { sample: Some(10) });
+    counter.on_datafeed(&device, &Datafeed::FrameBegin);
+    counter.on_datafeed(&device, &Datafeed::End);
+    assert_eq!(counter.triggers, vec![Some(10)]);
+    assert_eq!(counter.ends, 1);
+
+    let mut packets = 0;
+    (|_: &DriverInstance, _: &Datafeed| packets += 1).on_datafeed(&device, &Datafeed::End);
+    assert_eq!(packets, 1);
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use DriverInstance;
+
+// What a device looks like across rescans. Instance pointers change every scan,
+// so devices are matched on these fields instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+    pub driver: Option<String>,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub conn_id: Option<String>,
+}
+
+impl DeviceId {
+    // A string naming the device across reconnects and session file reloads,
+    // e.g. for keying presets. libsigrok assigns no such id, so it's built from
+    // the driver plus the most stable field available: the serial number, then
+    // the connection id (stable only while the device stays on the same port),
+    // then vendor and model (shared by identical devices).
+    pub fn stable_id(&self) -> String {
+        let driver = self.driver.as_ref().map(|x| x.as_str()).unwrap_or("user");
+        if let Some(ref serial) = self.serial_number {
+            format!("{}:serial={}", driver, serial)
+        } else if let Some(ref conn) = self.conn_id {
+            format!("{}:conn={}", driver, conn)
+        } else {
+            format!("{}:model={}/{}", driver,
+                self.vendor.as_ref().map(|x| x.as_str()).unwrap_or(""),
+                self.model.as_ref().map(|x| x.as_str()).unwrap_or(""))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DeviceChanges {
+    pub added: Vec<DriverInstance>,
+    pub removed: Vec<DriverInstance>,
+    pub retained: Vec<DriverInstance>,
+}
+
+// Tracks the devices present across repeated scans, e.g. to react to plugging
+// and unplugging an analyzer.
+#[derive(Debug, Default)]
+pub struct DeviceSet {
+    devices: Vec<(DeviceId, DriverInstance)>,
+}
+
+impl DeviceSet {
+    pub fn new() -> DeviceSet {
+        DeviceSet::default()
+    }
+
+    pub fn devices(&self) -> Vec<DriverInstance> {
+        self.devices.iter().map(|x| x.1.clone()).collect()
+    }
+
+    // Replaces the tracked devices with a fresh scan. Retained devices are
+    // reported (and kept) as their new instances.
+    pub fn update(&mut self, scan: Vec<DriverInstance>) -> DeviceChanges {
+        let scan: Vec<(DeviceId, DriverInstance)> = scan.into_iter().map(|x| (x.id(), x)).collect();
+        let (added, removed, retained) = {
+            let old: Vec<&DeviceId> = self.devices.iter().map(|x| &x.0).collect();
+            let new: Vec<&DeviceId> = scan.iter().map(|x| &x.0).collect();
+            diff(&old, &new)
+        };
+
+        let changes = DeviceChanges {
+            added: added.into_iter().map(|i| scan[i].1.clone()).collect(),
+            removed: removed.into_iter().map(|i| self.devices[i].1.clone()).collect(),
+            retained: retained.into_iter().map(|i| scan[i].1.clone()).collect(),
+        };
+        self.devices = scan;
+        changes
+    }
+}
+
+// Matches `new` against `old`, returning (added indices into new, removed
+// indices into old, retained indices into new). Equal keys are paired one to
+// one, so two identical devices without serial numbers are still counted.
+fn diff<K: Eq + Hash>(old: &[K], new: &[K]) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+    let mut unmatched: HashMap<&K, Vec<usize>> = HashMap::new();
+    for (i, key) in old.iter().enumerate().rev() {
+        unmatched.entry(key).or_insert_with(Vec::new).push(i);
+    }
+
+    let mut added = vec![];
+    let mut retained = vec![];
+    for (i, key) in new.iter().enumerate() {
+        match unmatched.get_mut(key).and_then(|x| x.pop()) {
+            Some(_) => retained.push(i),
+            None => added.push(i),
+        }
+    }
+
+    let mut removed: Vec<usize> = unmatched.into_iter().flat_map(|x| x.1).collect();
+    removed.sort();
+    (added, removed, retained)
+}
+
+#[test]
+fn it_diffs_scans() {
+    let empty: [&str; 0] = [];
+    assert_eq!(diff(&empty, &["fx2"]), (vec![0], vec![], vec![]));
+    assert_eq!(diff(&["fx2"], &["fx2", "dmm"]), (vec![1], vec![], vec![0]));
+    assert_eq!(diff(&["fx2", "dmm"], &["dmm"]), (vec![], vec![0], vec![0]));
+    assert_eq!(diff(&["dmm"], &empty), (vec![], vec![0], vec![]));
+
+    // Duplicate identities are paired one to one.
+    assert_eq!(diff(&["fx2", "fx2"], &["fx2"]), (vec![], vec![1], vec![0]));
+    assert_eq!(diff(&["fx2"], &["fx2", "fx2"]), (vec![1], vec![], vec![0]));
+}
+
+#[test]
+fn it_builds_stable_ids() {
+    let mut id = DeviceId {
+        driver: Some("fx2lafw".to_string()),
+        vendor: Some("Saleae".to_string()),
+        model: Some("Logic".to_string()),
+        serial_number: None,
+        conn_id: None,
+    };
+    assert_eq!(id.stable_id(), "fx2lafw:model=Saleae/Logic");
+    id.conn_id = Some("1.4".to_string());
+    assert_eq!(id.stable_id(), "fx2lafw:conn=1.4");
+    id.serial_number = Some("A1".to_string());
+    assert_eq!(id.stable_id(), "fx2lafw:serial=A1");
+}
@@ -0,0 +1,658 @@
+use sigrok_sys::{Enum_sr_trigger_matches, Enum_sr_unit, Enum_sr_mq, Enum_sr_configkey, Enum_sr_channeltype};
+use sigrok_sys::{Enum_sr_datatype, Enum_sr_keytype, Enum_sr_mqflag, Enum_sr_instance_type, sr_key_info_get};
+use sigrok_sys::{Enum_sr_loglevel, sr_key_info_name_get};
+use std::ffi::{CStr, CString};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+macro_rules! define_enum {
+    ($name:ident: $raw:ident { $($variant:ident => $value:ident,)* }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl $name {
+            pub fn from_raw(raw: u32) -> Option<$name> {
+                $(if raw == $raw::$value as u32 {
+                    return Some($name::$variant);
+                })*
+                None
+            }
+
+            pub fn to_raw(&self) -> u32 {
+                match *self {
+                    $($name::$variant => $raw::$value as u32,)*
+                }
+            }
+        }
+    }
+}
+
+define_enum!(TriggerType: Enum_sr_trigger_matches {
+    Zero => SR_TRIGGER_ZERO,
+    One => SR_TRIGGER_ONE,
+    Rising => SR_TRIGGER_RISING,
+    Falling => SR_TRIGGER_FALLING,
+    Edge => SR_TRIGGER_EDGE,
+    Over => SR_TRIGGER_OVER,
+    Under => SR_TRIGGER_UNDER,
+});
+
+// Single-character codes as used in sigrok-cli trigger specs ("D0=r,D1=1").
+impl fmt::Display for TriggerType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TriggerType::Zero => "0",
+            TriggerType::One => "1",
+            TriggerType::Rising => "r",
+            TriggerType::Falling => "f",
+            TriggerType::Edge => "e",
+            TriggerType::Over => "o",
+            TriggerType::Under => "u",
+        })
+    }
+}
+
+impl FromStr for TriggerType {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<TriggerType> {
+        match s {
+            "0" => Ok(TriggerType::Zero),
+            "1" => Ok(TriggerType::One),
+            "r" => Ok(TriggerType::Rising),
+            "f" => Ok(TriggerType::Falling),
+            "e" => Ok(TriggerType::Edge),
+            "o" => Ok(TriggerType::Over),
+            "u" => Ok(TriggerType::Under),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown trigger type {:?}", s))),
+        }
+    }
+}
+
+define_enum!(ChannelType: Enum_sr_channeltype {
+    Logic => SR_CHANNEL_LOGIC,
+    Analog => SR_CHANNEL_ANALOG,
+});
+
+define_enum!(InstType: Enum_sr_instance_type {
+    Usb => SR_INST_USB,
+    Serial => SR_INST_SERIAL,
+    Scpi => SR_INST_SCPI,
+    User => SR_INST_USER,
+    Modbus => SR_INST_MODBUS,
+});
+
+// libsigrok's log verbosity, from quietest to noisiest.
+define_enum!(LogLevel: Enum_sr_loglevel {
+    None => SR_LOG_NONE,
+    Error => SR_LOG_ERR,
+    Warn => SR_LOG_WARN,
+    Info => SR_LOG_INFO,
+    Debug => SR_LOG_DBG,
+    Spew => SR_LOG_SPEW,
+});
+
+// Ordered by verbosity, as libsigrok numbers them: None < Error < ... < Spew.
+impl PartialOrd for LogLevel {
+    fn partial_cmp(&self, other: &LogLevel) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogLevel {
+    fn cmp(&self, other: &LogLevel) -> Ordering {
+        self.to_raw().cmp(&other.to_raw())
+    }
+}
+
+define_enum!(Unit: Enum_sr_unit {
+    Volt => SR_UNIT_VOLT,
+    Ampere => SR_UNIT_AMPERE,
+    Ohm => SR_UNIT_OHM,
+    Farad => SR_UNIT_FARAD,
+    Kelvin => SR_UNIT_KELVIN,
+    Celsius => SR_UNIT_CELSIUS,
+    Fahrenheit => SR_UNIT_FAHRENHEIT,
+    Hertz => SR_UNIT_HERTZ,
+    Percentage => SR_UNIT_PERCENTAGE,
+    Boolean => SR_UNIT_BOOLEAN,
+    Second => SR_UNIT_SECOND,
+    Siemens => SR_UNIT_SIEMENS,
+    DecibelMw => SR_UNIT_DECIBEL_MW,
+    DecibelVolt => SR_UNIT_DECIBEL_VOLT,
+    Unitless => SR_UNIT_UNITLESS,
+    DecibelSpl => SR_UNIT_DECIBEL_SPL,
+    Concentration => SR_UNIT_CONCENTRATION,
+    RevolutionsPerMinute => SR_UNIT_REVOLUTIONS_PER_MINUTE,
+    VoltAmpere => SR_UNIT_VOLT_AMPERE,
+    Watt => SR_UNIT_WATT,
+    WattHour => SR_UNIT_WATT_HOUR,
+    MeterSecond => SR_UNIT_METER_SECOND,
+    Hectopascal => SR_UNIT_HECTOPASCAL,
+    Humidity293k => SR_UNIT_HUMIDITY_293K,
+    Degree => SR_UNIT_DEGREE,
+    Henry => SR_UNIT_HENRY,
+    Gram => SR_UNIT_GRAM,
+    Carat => SR_UNIT_CARAT,
+    Ounce => SR_UNIT_OUNCE,
+    TroyOunce => SR_UNIT_TROY_OUNCE,
+    Pound => SR_UNIT_POUND,
+    Pennyweight => SR_UNIT_PENNYWEIGHT,
+    Grain => SR_UNIT_GRAIN,
+    Tael => SR_UNIT_TAEL,
+    Momme => SR_UNIT_MOMME,
+    Tola => SR_UNIT_TOLA,
+    Piece => SR_UNIT_PIECE,
+});
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Unit::Volt => "V",
+            Unit::Ampere => "A",
+            Unit::Ohm => "\u{03A9}",
+            Unit::Farad => "F",
+            Unit::Kelvin => "K",
+            Unit::Celsius => "\u{00B0}C",
+            Unit::Fahrenheit => "\u{00B0}F",
+            Unit::Hertz => "Hz",
+            Unit::Percentage => "%",
+            Unit::Boolean => "",
+            Unit::Second => "s",
+            Unit::Siemens => "S",
+            Unit::DecibelMw => "dBm",
+            Unit::DecibelVolt => "dBV",
+            Unit::Unitless => "",
+            Unit::DecibelSpl => "dB",
+            Unit::Concentration => "ppm",
+            Unit::RevolutionsPerMinute => "RPM",
+            Unit::VoltAmpere => "VA",
+            Unit::Watt => "W",
+            Unit::WattHour => "Wh",
+            Unit::MeterSecond => "m/s",
+            Unit::Hectopascal => "hPa",
+            Unit::Humidity293k => "%rF",
+            Unit::Degree => "\u{00B0}",
+            Unit::Henry => "H",
+            Unit::Gram => "g",
+            Unit::Carat => "ct",
+            Unit::Ounce => "oz",
+            Unit::TroyOunce => "oz t",
+            Unit::Pound => "lb",
+            Unit::Pennyweight => "dwt",
+            Unit::Grain => "gr",
+            Unit::Tael => "tael",
+            Unit::Momme => "momme",
+            Unit::Tola => "tola",
+            Unit::Piece => "pcs",
+        })
+    }
+}
+
+impl Unit {
+    // Stable machine-readable identifier, e.g. "volt", derived from libsigrok's
+    // constant names. Unlike the Display symbols these are meant for storage and
+    // APIs, and won't change between versions.
+    pub fn id(&self) -> &'static str {
+        match *self {
+            Unit::Volt => "volt",
+            Unit::Ampere => "ampere",
+            Unit::Ohm => "ohm",
+            Unit::Farad => "farad",
+            Unit::Kelvin => "kelvin",
+            Unit::Celsius => "celsius",
+            Unit::Fahrenheit => "fahrenheit",
+            Unit::Hertz => "hertz",
+            Unit::Percentage => "percentage",
+            Unit::Boolean => "boolean",
+            Unit::Second => "second",
+            Unit::Siemens => "siemens",
+            Unit::DecibelMw => "decibel_mw",
+            Unit::DecibelVolt => "decibel_volt",
+            Unit::Unitless => "unitless",
+            Unit::DecibelSpl => "decibel_spl",
+            Unit::Concentration => "concentration",
+            Unit::RevolutionsPerMinute => "revolutions_per_minute",
+            Unit::VoltAmpere => "volt_ampere",
+            Unit::Watt => "watt",
+            Unit::WattHour => "watt_hour",
+            Unit::MeterSecond => "meter_second",
+            Unit::Hectopascal => "hectopascal",
+            Unit::Humidity293k => "humidity_293k",
+            Unit::Degree => "degree",
+            Unit::Henry => "henry",
+            Unit::Gram => "gram",
+            Unit::Carat => "carat",
+            Unit::Ounce => "ounce",
+            Unit::TroyOunce => "troy_ounce",
+            Unit::Pound => "pound",
+            Unit::Pennyweight => "pennyweight",
+            Unit::Grain => "grain",
+            Unit::Tael => "tael",
+            Unit::Momme => "momme",
+            Unit::Tola => "tola",
+            Unit::Piece => "piece",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Unit> {
+        ALL_UNITS.iter().cloned().find(|x| x.id() == id)
+    }
+}
+
+const ALL_UNITS: &'static [Unit] = &[
+    Unit::Volt,
+    Unit::Ampere,
+    Unit::Ohm,
+    Unit::Farad,
+    Unit::Kelvin,
+    Unit::Celsius,
+    Unit::Fahrenheit,
+    Unit::Hertz,
+    Unit::Percentage,
+    Unit::Boolean,
+    Unit::Second,
+    Unit::Siemens,
+    Unit::DecibelMw,
+    Unit::DecibelVolt,
+    Unit::Unitless,
+    Unit::DecibelSpl,
+    Unit::Concentration,
+    Unit::RevolutionsPerMinute,
+    Unit::VoltAmpere,
+    Unit::Watt,
+    Unit::WattHour,
+    Unit::MeterSecond,
+    Unit::Hectopascal,
+    Unit::Humidity293k,
+    Unit::Degree,
+    Unit::Henry,
+    Unit::Gram,
+    Unit::Carat,
+    Unit::Ounce,
+    Unit::TroyOunce,
+    Unit::Pound,
+    Unit::Pennyweight,
+    Unit::Grain,
+    Unit::Tael,
+    Unit::Momme,
+    Unit::Tola,
+    Unit::Piece,
+];
+
+define_enum!(Mq: Enum_sr_mq {
+    Voltage => SR_MQ_VOLTAGE,
+    Current => SR_MQ_CURRENT,
+    Resistance => SR_MQ_RESISTANCE,
+    Capacitance => SR_MQ_CAPACITANCE,
+    Temperature => SR_MQ_TEMPERATURE,
+    Frequency => SR_MQ_FREQUENCY,
+    DutyCycle => SR_MQ_DUTY_CYCLE,
+    Continuity => SR_MQ_CONTINUITY,
+    PulseWidth => SR_MQ_PULSE_WIDTH,
+    Conductance => SR_MQ_CONDUCTANCE,
+    Power => SR_MQ_POWER,
+    Gain => SR_MQ_GAIN,
+    SoundPressureLevel => SR_MQ_SOUND_PRESSURE_LEVEL,
+    CarbonMonoxide => SR_MQ_CARBON_MONOXIDE,
+    RelativeHumidity => SR_MQ_RELATIVE_HUMIDITY,
+    Time => SR_MQ_TIME,
+    WindSpeed => SR_MQ_WIND_SPEED,
+    Pressure => SR_MQ_PRESSURE,
+    ParallelInductance => SR_MQ_PARALLEL_INDUCTANCE,
+    ParallelCapacitance => SR_MQ_PARALLEL_CAPACITANCE,
+    ParallelResistance => SR_MQ_PARALLEL_RESISTANCE,
+    SeriesInductance => SR_MQ_SERIES_INDUCTANCE,
+    SeriesCapacitance => SR_MQ_SERIES_CAPACITANCE,
+    SeriesResistance => SR_MQ_SERIES_RESISTANCE,
+    DissipationFactor => SR_MQ_DISSIPATION_FACTOR,
+    QualityFactor => SR_MQ_QUALITY_FACTOR,
+    PhaseAngle => SR_MQ_PHASE_ANGLE,
+    Difference => SR_MQ_DIFFERENCE,
+    Count => SR_MQ_COUNT,
+    PowerFactor => SR_MQ_POWER_FACTOR,
+    ApparentPower => SR_MQ_APPARENT_POWER,
+    Mass => SR_MQ_MASS,
+    HarmonicRatio => SR_MQ_HARMONIC_RATIO,
+});
+
+impl Mq {
+    // Stable machine-readable identifier, e.g. "voltage", derived from libsigrok's
+    // constant names.
+    pub fn id(&self) -> &'static str {
+        match *self {
+            Mq::Voltage => "voltage",
+            Mq::Current => "current",
+            Mq::Resistance => "resistance",
+            Mq::Capacitance => "capacitance",
+            Mq::Temperature => "temperature",
+            Mq::Frequency => "frequency",
+            Mq::DutyCycle => "duty_cycle",
+            Mq::Continuity => "continuity",
+            Mq::PulseWidth => "pulse_width",
+            Mq::Conductance => "conductance",
+            Mq::Power => "power",
+            Mq::Gain => "gain",
+            Mq::SoundPressureLevel => "sound_pressure_level",
+            Mq::CarbonMonoxide => "carbon_monoxide",
+            Mq::RelativeHumidity => "relative_humidity",
+            Mq::Time => "time",
+            Mq::WindSpeed => "wind_speed",
+            Mq::Pressure => "pressure",
+            Mq::ParallelInductance => "parallel_inductance",
+            Mq::ParallelCapacitance => "parallel_capacitance",
+            Mq::ParallelResistance => "parallel_resistance",
+            Mq::SeriesInductance => "series_inductance",
+            Mq::SeriesCapacitance => "series_capacitance",
+            Mq::SeriesResistance => "series_resistance",
+            Mq::DissipationFactor => "dissipation_factor",
+            Mq::QualityFactor => "quality_factor",
+            Mq::PhaseAngle => "phase_angle",
+            Mq::Difference => "difference",
+            Mq::Count => "count",
+            Mq::PowerFactor => "power_factor",
+            Mq::ApparentPower => "apparent_power",
+            Mq::Mass => "mass",
+            Mq::HarmonicRatio => "harmonic_ratio",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Mq> {
+        ALL_MQS.iter().cloned().find(|x| x.id() == id)
+    }
+}
+
+const ALL_MQS: &'static [Mq] = &[
+    Mq::Voltage,
+    Mq::Current,
+    Mq::Resistance,
+    Mq::Capacitance,
+    Mq::Temperature,
+    Mq::Frequency,
+    Mq::DutyCycle,
+    Mq::Continuity,
+    Mq::PulseWidth,
+    Mq::Conductance,
+    Mq::Power,
+    Mq::Gain,
+    Mq::SoundPressureLevel,
+    Mq::CarbonMonoxide,
+    Mq::RelativeHumidity,
+    Mq::Time,
+    Mq::WindSpeed,
+    Mq::Pressure,
+    Mq::ParallelInductance,
+    Mq::ParallelCapacitance,
+    Mq::ParallelResistance,
+    Mq::SeriesInductance,
+    Mq::SeriesCapacitance,
+    Mq::SeriesResistance,
+    Mq::DissipationFactor,
+    Mq::QualityFactor,
+    Mq::PhaseAngle,
+    Mq::Difference,
+    Mq::Count,
+    Mq::PowerFactor,
+    Mq::ApparentPower,
+    Mq::Mass,
+    Mq::HarmonicRatio,
+];
+
+define_enum!(MqFlag: Enum_sr_mqflag {
+    Ac => SR_MQFLAG_AC,
+    Dc => SR_MQFLAG_DC,
+    Rms => SR_MQFLAG_RMS,
+    Diode => SR_MQFLAG_DIODE,
+    Hold => SR_MQFLAG_HOLD,
+    Max => SR_MQFLAG_MAX,
+    Min => SR_MQFLAG_MIN,
+    Autorange => SR_MQFLAG_AUTORANGE,
+    Relative => SR_MQFLAG_RELATIVE,
+    SplFreqWeightA => SR_MQFLAG_SPL_FREQ_WEIGHT_A,
+    SplFreqWeightC => SR_MQFLAG_SPL_FREQ_WEIGHT_C,
+    SplFreqWeightZ => SR_MQFLAG_SPL_FREQ_WEIGHT_Z,
+    SplFreqWeightFlat => SR_MQFLAG_SPL_FREQ_WEIGHT_FLAT,
+    SplTimeWeightS => SR_MQFLAG_SPL_TIME_WEIGHT_S,
+    SplTimeWeightF => SR_MQFLAG_SPL_TIME_WEIGHT_F,
+    SplLat => SR_MQFLAG_SPL_LAT,
+    SplPctOverAlarm => SR_MQFLAG_SPL_PCT_OVER_ALARM,
+    Duration => SR_MQFLAG_DURATION,
+    Avg => SR_MQFLAG_AVG,
+    Reference => SR_MQFLAG_REFERENCE,
+    Unstable => SR_MQFLAG_UNSTABLE,
+    FourWire => SR_MQFLAG_FOUR_WIRE,
+});
+
+impl MqFlag {
+    // Splits a raw mqflags bitmask into its known flags.
+    pub fn from_bits(bits: u64) -> Vec<MqFlag> {
+        (0..64)
+            .filter(|&i| bits & (1u64 << i) != 0)
+            .filter_map(|i| if i < 32 { MqFlag::from_raw(1u32 << i) } else { None })
+            .collect()
+    }
+}
+
+define_enum!(Function: Enum_sr_configkey {
+    LogicAnalyzer => SR_CONF_LOGIC_ANALYZER,
+    Oscilloscope => SR_CONF_OSCILLOSCOPE,
+    Multimeter => SR_CONF_MULTIMETER,
+    DemoDev => SR_CONF_DEMO_DEV,
+    SoundLevelMeter => SR_CONF_SOUNDLEVELMETER,
+    Thermometer => SR_CONF_THERMOMETER,
+    Hygrometer => SR_CONF_HYGROMETER,
+    EnergyMeter => SR_CONF_ENERGYMETER,
+    Demodulator => SR_CONF_DEMODULATOR,
+    PowerSupply => SR_CONF_POWER_SUPPLY,
+    LcrMeter => SR_CONF_LCRMETER,
+    ElectronicLoad => SR_CONF_ELECTRONIC_LOAD,
+    Scale => SR_CONF_SCALE,
+    SignalGenerator => SR_CONF_SIGNAL_GENERATOR,
+    PowerMeter => SR_CONF_POWERMETER,
+});
+
+impl Function {
+    // Names drivers commonly use for this function in SR_CONF_DEVICE_MODE.
+    fn mode_names(&self) -> &'static [&'static str] {
+        match *self {
+            Function::LogicAnalyzer => &["logic analyzer", "logic"],
+            Function::Oscilloscope => &["oscilloscope", "scope"],
+            Function::Multimeter => &["multimeter", "dmm"],
+            Function::DemoDev => &["demo"],
+            Function::SoundLevelMeter => &["sound level meter", "slm"],
+            Function::Thermometer => &["thermometer", "temperature", "temp"],
+            Function::Hygrometer => &["hygrometer", "humidity"],
+            Function::EnergyMeter => &["energy meter", "energy"],
+            Function::Demodulator => &["demodulator"],
+            Function::PowerSupply => &["power supply", "psu"],
+            Function::LcrMeter => &["lcr meter", "lcr"],
+            Function::ElectronicLoad => &["electronic load", "load"],
+            Function::Scale => &["scale"],
+            Function::SignalGenerator => &["signal generator", "function generator", "generator", "awg"],
+            Function::PowerMeter => &["power meter"],
+        }
+    }
+
+    // Case-insensitive; '_' and '-' count as spaces.
+    pub(crate) fn matches_mode(&self, mode: &str) -> bool {
+        let mode = mode.trim().to_lowercase().replace(|c| c == '_' || c == '-', " ");
+        self.mode_names().iter().any(|&x| x == mode)
+    }
+}
+
+define_enum!(ConfigKey: Enum_sr_configkey {
+    SampleRate => SR_CONF_SAMPLERATE,
+    CaptureRatio => SR_CONF_CAPTURE_RATIO,
+    PatternMode => SR_CONF_PATTERN_MODE,
+    Rle => SR_CONF_RLE,
+    TriggerSlope => SR_CONF_TRIGGER_SLOPE,
+    Averaging => SR_CONF_AVERAGING,
+    AvgSamples => SR_CONF_AVG_SAMPLES,
+    TriggerSource => SR_CONF_TRIGGER_SOURCE,
+    HorizTriggerPos => SR_CONF_HORIZ_TRIGGERPOS,
+    BufferSize => SR_CONF_BUFFERSIZE,
+    Timebase => SR_CONF_TIMEBASE,
+    Filter => SR_CONF_FILTER,
+    Vdiv => SR_CONF_VDIV,
+    Coupling => SR_CONF_COUPLING,
+    TriggerMatch => SR_CONF_TRIGGER_MATCH,
+    SampleInterval => SR_CONF_SAMPLE_INTERVAL,
+    NumHdiv => SR_CONF_NUM_HDIV,
+    NumVdiv => SR_CONF_NUM_VDIV,
+    SplWeightFreq => SR_CONF_SPL_WEIGHT_FREQ,
+    SplWeightTime => SR_CONF_SPL_WEIGHT_TIME,
+    SplMeasurementRange => SR_CONF_SPL_MEASUREMENT_RANGE,
+    HoldMax => SR_CONF_HOLD_MAX,
+    HoldMin => SR_CONF_HOLD_MIN,
+    VoltageThreshold => SR_CONF_VOLTAGE_THRESHOLD,
+    ExternalClock => SR_CONF_EXTERNAL_CLOCK,
+    Swap => SR_CONF_SWAP,
+    CenterFrequency => SR_CONF_CENTER_FREQUENCY,
+    NumLogicChannels => SR_CONF_NUM_LOGIC_CHANNELS,
+    NumAnalogChannels => SR_CONF_NUM_ANALOG_CHANNELS,
+    Voltage => SR_CONF_VOLTAGE,
+    VoltageTarget => SR_CONF_VOLTAGE_TARGET,
+    Current => SR_CONF_CURRENT,
+    CurrentLimit => SR_CONF_CURRENT_LIMIT,
+    Enabled => SR_CONF_ENABLED,
+    ChannelConfig => SR_CONF_CHANNEL_CONFIG,
+    OverVoltageProtectionEnabled => SR_CONF_OVER_VOLTAGE_PROTECTION_ENABLED,
+    OverVoltageProtectionActive => SR_CONF_OVER_VOLTAGE_PROTECTION_ACTIVE,
+    OverVoltageProtectionThreshold => SR_CONF_OVER_VOLTAGE_PROTECTION_THRESHOLD,
+    OverCurrentProtectionEnabled => SR_CONF_OVER_CURRENT_PROTECTION_ENABLED,
+    OverCurrentProtectionActive => SR_CONF_OVER_CURRENT_PROTECTION_ACTIVE,
+    OverCurrentProtectionThreshold => SR_CONF_OVER_CURRENT_PROTECTION_THRESHOLD,
+    ClockEdge => SR_CONF_CLOCK_EDGE,
+    Amplitude => SR_CONF_AMPLITUDE,
+    Regulation => SR_CONF_REGULATION,
+    OverTemperatureProtection => SR_CONF_OVER_TEMPERATURE_PROTECTION,
+    OutputFrequency => SR_CONF_OUTPUT_FREQUENCY,
+    OutputFrequencyTarget => SR_CONF_OUTPUT_FREQUENCY_TARGET,
+    MeasuredQuantity => SR_CONF_MEASURED_QUANTITY,
+    EquivCircuitModel => SR_CONF_EQUIV_CIRCUIT_MODEL,
+    OverTemperatureProtectionActive => SR_CONF_OVER_TEMPERATURE_PROTECTION_ACTIVE,
+    UnderVoltageCondition => SR_CONF_UNDER_VOLTAGE_CONDITION,
+    UnderVoltageConditionActive => SR_CONF_UNDER_VOLTAGE_CONDITION_ACTIVE,
+    TriggerLevel => SR_CONF_TRIGGER_LEVEL,
+    UnderVoltageConditionThreshold => SR_CONF_UNDER_VOLTAGE_CONDITION_THRESHOLD,
+    ExternalClockSource => SR_CONF_EXTERNAL_CLOCK_SOURCE,
+    Offset => SR_CONF_OFFSET,
+    TriggerPattern => SR_CONF_TRIGGER_PATTERN,
+    HighResolution => SR_CONF_HIGH_RESOLUTION,
+    PeakDetection => SR_CONF_PEAK_DETECTION,
+    LogicThreshold => SR_CONF_LOGIC_THRESHOLD,
+    LogicThresholdCustom => SR_CONF_LOGIC_THRESHOLD_CUSTOM,
+    Range => SR_CONF_RANGE,
+    Digits => SR_CONF_DIGITS,
+    SessionFile => SR_CONF_SESSIONFILE,
+    CaptureFile => SR_CONF_CAPTUREFILE,
+    CaptureUnitSize => SR_CONF_CAPTURE_UNITSIZE,
+    PowerOff => SR_CONF_POWER_OFF,
+    DataSource => SR_CONF_DATA_SOURCE,
+    ProbeFactor => SR_CONF_PROBE_FACTOR,
+    AdcPowerlineCycles => SR_CONF_ADC_POWERLINE_CYCLES,
+    LimitMsec => SR_CONF_LIMIT_MSEC,
+    LimitSamples => SR_CONF_LIMIT_SAMPLES,
+    LimitFrames => SR_CONF_LIMIT_FRAMES,
+    Continuous => SR_CONF_CONTINUOUS,
+    Datalog => SR_CONF_DATALOG,
+    DeviceMode => SR_CONF_DEVICE_MODE,
+    TestMode => SR_CONF_TEST_MODE,
+});
+
+define_enum!(ConfigDataType: Enum_sr_datatype {
+    UInt64 => SR_T_UINT64,
+    String => SR_T_STRING,
+    Bool => SR_T_BOOL,
+    Float => SR_T_FLOAT,
+    RationalPeriod => SR_T_RATIONAL_PERIOD,
+    RationalVolt => SR_T_RATIONAL_VOLT,
+    KeyValue => SR_T_KEYVALUE,
+    UInt64Range => SR_T_UINT64_RANGE,
+    DoubleRange => SR_T_DOUBLE_RANGE,
+    Int32 => SR_T_INT32,
+    Mq => SR_T_MQ,
+});
+
+impl ConfigKey {
+    // The value type libsigrok declares for this key in its key info table.
+    pub fn data_type(&self) -> Option<ConfigDataType> {
+        unsafe {
+            let info = sr_key_info_get(Enum_sr_keytype::SR_KEY_CONFIG as i32, self.to_raw());
+            if info.is_null() {
+                None
+            } else {
+                ConfigDataType::from_raw((*info).datatype as u32)
+            }
+        }
+    }
+
+    // libsigrok's stable short name for the key, e.g. "samplerate". These are
+    // what sigrok-cli accepts, so they're safe to store in files.
+    pub fn id(&self) -> Option<String> {
+        unsafe {
+            let info = sr_key_info_get(Enum_sr_keytype::SR_KEY_CONFIG as i32, self.to_raw());
+            if info.is_null() || (*info).id.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr((*info).id).to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<ConfigKey> {
+        let id = CString::new(id).ok()?;
+        unsafe {
+            let info = sr_key_info_name_get(Enum_sr_keytype::SR_KEY_CONFIG as i32, id.as_ptr());
+            if info.is_null() {
+                None
+            } else {
+                ConfigKey::from_raw((*info).key as u32)
+            }
+        }
+    }
+}
+
+#[test]
+fn it_matches_device_modes() {
+    assert!(Function::Multimeter.matches_mode("DMM"));
+    assert!(Function::Thermometer.matches_mode("Temperature"));
+    assert!(Function::SignalGenerator.matches_mode("function_generator"));
+    assert!(Function::PowerSupply.matches_mode(" Power-Supply "));
+    assert!(!Function::Multimeter.matches_mode("Thermometer"));
+}
+
+#[test]
+fn it_round_trips_quantity_ids() {
+    assert_eq!(Unit::Volt.id(), "volt");
+    assert_eq!(Unit::DecibelMw.id(), "decibel_mw");
+    assert_eq!(Mq::DutyCycle.id(), "duty_cycle");
+    for unit in ALL_UNITS {
+        assert_eq!(Unit::from_id(unit.id()), Some(*unit));
+    }
+    for mq in ALL_MQS {
+        assert_eq!(Mq::from_id(mq.id()), Some(*mq));
+    }
+    assert_eq!(Unit::from_id("V"), None);
+}
+
+#[test]
+fn it_round_trips_trigger_codes() {
+    for code in &["0", "1", "r", "f", "e", "o", "u"] {
+        let kind: TriggerType = code.parse().unwrap();
+        assert_eq!(kind.to_string(), *code);
+    }
+    assert!("x".parse::<TriggerType>().is_err());
+    assert!("".parse::<TriggerType>().is_err());
+}
+
+#[test]
+fn it_orders_log_levels() {
+    assert!(LogLevel::Info >= LogLevel::Warn);
+    assert!(LogLevel::None < LogLevel::Error);
+    assert_eq!(LogLevel::Spew.max(LogLevel::Debug), LogLevel::Spew);
+}
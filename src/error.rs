@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_int;
+use sigrok_sys::sr_strerror;
+use ConfigKey;
+
+// A libsigrok return code (SR_ERR_*). Shown with libsigrok's own description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrError(pub c_int);
+
+impl fmt::Display for SrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = unsafe { sr_strerror(self.0) };
+        if msg.is_null() {
+            write!(f, "libsigrok error {}", self.0)
+        } else {
+            write!(f, "{}", unsafe { CStr::from_ptr(msg) }.to_string_lossy())
+        }
+    }
+}
+
+impl Error for SrError {}
+
+// A failed config access, carrying the key and the underlying cause. It's
+// returned inside an io::Error, so source() on that error reaches `cause`.
+#[derive(Debug)]
+pub struct ConfigError {
+    key: ConfigKey,
+    cause: Box<Error + Send + Sync>,
+}
+
+impl ConfigError {
+    pub fn new<E: Into<Box<Error + Send + Sync>>>(key: ConfigKey, cause: E) -> ConfigError {
+        ConfigError {
+            key,
+            cause: cause.into(),
+        }
+    }
+
+    pub fn key(&self) -> ConfigKey {
+        self.key
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not set {:?}", self.key)
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        Some(&*self.cause)
+    }
+}
+
+#[test]
+fn it_chains_config_errors() {
+    let err = ::std::io::Error::new(::std::io::ErrorKind::Other, ConfigError::new(ConfigKey::SampleRate, SrError(-1)));
+    let inner = err.get_ref().and_then(|x| x.downcast_ref::<ConfigError>()).unwrap();
+    assert_eq!(inner.key(), ConfigKey::SampleRate);
+    let cause = err.source().and_then(|x| x.downcast_ref::<SrError>());
+    assert_eq!(cause, Some(&SrError(-1)));
+}
@@ -0,0 +1,62 @@
+use std::io;
+use Datafeed;
+
+#[cfg(feature = "ndarray")]
+use ndarray::Array2;
+
+// Accumulates the analog packets between a FrameBegin and FrameEnd, demuxed
+// per channel. Channels are kept in the order they first appear in the frame.
+#[derive(Debug, Default)]
+pub struct FrameCollector {
+    channels: Vec<(String, Vec<f32>)>,
+    in_frame: bool,
+    complete: bool,
+}
+
+impl FrameCollector {
+    pub fn new() -> FrameCollector {
+        FrameCollector::default()
+    }
+
+    // Returns true once a full frame has been collected.
+    pub fn push(&mut self, data: &Datafeed) -> io::Result<bool> {
+        match data {
+            &Datafeed::FrameBegin => {
+                self.channels.clear();
+                self.in_frame = true;
+                self.complete = false;
+            }
+            &Datafeed::FrameEnd => {
+                if self.in_frame {
+                    self.in_frame = false;
+                    self.complete = true;
+                }
+            }
+            &Datafeed::Analog(ref analog) if self.in_frame => analog.demux_into(&mut self.channels)?,
+            _ => { }
+        }
+        Ok(self.complete)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn channels(&self) -> &[(String, Vec<f32>)] {
+        &self.channels
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<f32>> {
+        self.channels.iter().map(|x| x.1.clone()).collect()
+    }
+
+    // Row = channel (in channels() order), column = sample. Channels with fewer
+    // samples than the longest one are padded with NaN.
+    #[cfg(feature = "ndarray")]
+    pub fn to_matrix(&self) -> Array2<f32> {
+        let cols = self.channels.iter().map(|x| x.1.len()).max().unwrap_or(0);
+        Array2::from_shape_fn((self.channels.len(), cols), |(r, c)| {
+            self.channels[r].1.get(c).cloned().unwrap_or(::std::f32::NAN)
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use sigrok_sys::{Struct_sr_input, Struct_sr_input_module, Struct_sr_option};
+use sigrok_sys::{sr_input_find, sr_input_options_get, sr_input_options_free, sr_input_new};
+use sigrok_sys::{sr_input_send, sr_input_end, sr_input_dev_inst_get, sr_input_free};
+use glib_sys::{self, GVariant};
+use std::ffi::{CStr, CString};
+use std::io;
+use std::ptr;
+use {variant_type, DriverInstance};
+
+// Feeds a file through one of libsigrok's input modules ("csv", "vcd", ...),
+// which turns it into a device whose packets are sent to the session it's
+// attached to.
+//
+// Modules set up their device on the first send() without emitting data, so
+// the usual order is: send a first chunk, attach device() to a session with a
+// callback, send the remaining chunks, then end().
+pub struct Input {
+    context: *mut Struct_sr_input,
+}
+
+impl Input {
+    // Options are given as strings and converted to the type of each option's
+    // default value, e.g. ("samplerate", "1000000") or ("header", "true").
+    pub fn new(format: &str, options: &[(&str, &str)]) -> io::Result<Input> {
+        let format = CString::new(format)?;
+        unsafe {
+            let module = sr_input_find(format.as_ptr() as *mut _);
+            if module.is_null() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "Unknown input format"));
+            }
+            let table = glib_sys::g_hash_table_new_full(Some(glib_sys::g_str_hash), Some(glib_sys::g_str_equal),
+                                                        Some(glib_sys::g_free), Some(unref_variant));
+            let res = fill_options(module, table, options);
+            let context = if res.is_ok() {
+                sr_input_new(module, table)
+            } else {
+                ptr::null_mut()
+            };
+            glib_sys::g_hash_table_destroy(table);
+            res?;
+            if context.is_null() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Could not create input"));
+            }
+            Ok(Input {
+                context,
+            })
+        }
+    }
+
+    pub fn send(&self, data: &[u8]) -> io::Result<()> {
+        unsafe {
+            let buf = glib_sys::g_string_new_len(data.as_ptr() as *const _, data.len() as isize);
+            let res = sr_input_send(self.context, buf);
+            glib_sys::g_string_free(buf, 1);
+            if res != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Input module rejected data"));
+            }
+        }
+        Ok(())
+    }
+
+    // Flushes anything the module still buffers.
+    pub fn end(&self) -> io::Result<()> {
+        unsafe {
+            if sr_input_end(self.context) != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Could not finish input"));
+            }
+        }
+        Ok(())
+    }
+
+    // None until the module has seen enough data to set up its device.
+    pub fn device(&self) -> Option<DriverInstance> {
+        unsafe {
+            let sdi = sr_input_dev_inst_get(self.context);
+            if sdi.is_null() {
+                None
+            } else {
+                Some(DriverInstance {
+                    context: sdi as *mut _,
+                })
+            }
+        }
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        unsafe {
+            sr_input_free(self.context);
+        }
+    }
+}
+
+unsafe extern "C" fn unref_variant(data: glib_sys::gpointer) {
+    glib_sys::g_variant_unref(data as *mut GVariant);
+}
+
+unsafe fn fill_options(module: *const Struct_sr_input_module, table: *mut glib_sys::GHashTable,
+                       options: &[(&str, &str)]) -> io::Result<()> {
+    if options.is_empty() {
+        return Ok(());
+    }
+    let defaults = sr_input_options_get(module);
+    let res = insert_options(defaults, table, options);
+    if !defaults.is_null() {
+        sr_input_options_free(defaults);
+    }
+    res
+}
+
+unsafe fn insert_options(defaults: *mut *const Struct_sr_option, table: *mut glib_sys::GHashTable,
+                         options: &[(&str, &str)]) -> io::Result<()> {
+    for &(id, value) in options {
+        let default = find_option(defaults, id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown input option {:?}", id)))?;
+        let gvar = option_variant(&variant_type(default), value)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid value for input option {:?}", id)))?;
+        let key = CString::new(id)?;
+        glib_sys::g_hash_table_insert(table, glib_sys::g_strdup(key.as_ptr()) as glib_sys::gpointer,
+                                      glib_sys::g_variant_ref_sink(gvar) as glib_sys::gpointer);
+    }
+    Ok(())
+}
+
+unsafe fn find_option(mut options: *mut *const Struct_sr_option, id: &str) -> Option<*mut GVariant> {
+    if options.is_null() {
+        return None;
+    }
+    while !(*options).is_null() {
+        let option = *options;
+        if CStr::from_ptr((*option).id).to_bytes() == id.as_bytes() && !(*option).def.is_null() {
+            return Some((*option).def);
+        }
+        options = options.offset(1);
+    }
+    None
+}
+
+unsafe fn option_variant(signature: &str, value: &str) -> Option<*mut GVariant> {
+    match signature {
+        "s" => CString::new(value).ok().map(|x| glib_sys::g_variant_new_string(x.as_ptr())),
+        "i" => value.parse().ok().map(|x| glib_sys::g_variant_new_int32(x)),
+        "u" => value.parse().ok().map(|x| glib_sys::g_variant_new_uint32(x)),
+        "t" => value.parse().ok().map(|x| glib_sys::g_variant_new_uint64(x)),
+        "d" => value.parse().ok().map(|x| glib_sys::g_variant_new_double(x)),
+        "b" => value.parse::<bool>().ok().map(|x| glib_sys::g_variant_new_boolean(x as glib_sys::gboolean)),
+        _ => None,
+    }
+}
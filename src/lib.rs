@@ -1,6 +1,17 @@
 extern crate sigrok_sys;
 extern crate glib_sys;
 extern crate time;
+extern crate num_rational;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[cfg(feature = "memmap2")]
+extern crate memmap2;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(test)]
+extern crate serde_json;
 
 use sigrok_sys::{Struct_sr_context, sr_init, sr_exit, sr_driver_list, Struct_sr_dev_driver};
 use sigrok_sys::{sr_dev_list, sr_driver_init, sr_driver_scan, Struct_sr_dev_inst};
@@ -8,18 +19,96 @@ use sigrok_sys::{sr_dev_inst_channels_get, Struct_sr_channel};
 use sigrok_sys::{sr_session_new, Struct_sr_session, sr_dev_open};
 use sigrok_sys::{sr_session_datafeed_callback_add, Struct_sr_datafeed_packet, sr_session_dev_add};
 use sigrok_sys::{sr_dev_channel_enable, sr_session_start, Enum_sr_packettype};
-use sigrok_sys::{Struct_sr_datafeed_logic, Enum_sr_configkey, Struct_sr_channel_group};
+use sigrok_sys::{Struct_sr_datafeed_logic, Struct_sr_channel_group};
 use sigrok_sys::{sr_dev_inst_channel_groups_get, sr_config_set, Struct_sr_datafeed_header};
+use sigrok_sys::{sr_config_list, sr_dev_inst_driver_get};
+use sigrok_sys::{sr_session_dev_remove, sr_session_dev_list, sr_dev_close};
 use std::mem;
+use std::fmt;
+use std::env;
 use std::io;
 use std::ffi::{CStr, CString};
 use std::os;
 use std::slice;
-use glib_sys::{GSList, g_main_loop_new, g_main_loop_run};
+use std::ptr;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::thread;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::ops::Deref;
+use std::collections::HashMap;
+use sigrok_sys::{sr_session_trigger_set, Enum_sr_channeltype, Struct_sr_datafeed_analog};
+use sigrok_sys::{sr_dev_options, sr_config_get, sr_dev_config_capabilities_list, Enum_sr_configcap};
+use sigrok_sys::{sr_dev_inst_connid_get, sr_session_is_running};
+use sigrok_sys::{sr_dev_inst_user_new, sr_dev_inst_channel_add, sr_dev_inst_vendor_get};
+use sigrok_sys::{sr_dev_inst_model_get, sr_dev_inst_version_get, sr_dev_inst_sernum_get};
+use sigrok_sys::{sr_session_load, sr_session_stop, Struct_sr_config, Enum_sr_configkey};
+use sigrok_sys::{sr_session_run, sr_session_datafeed_callback_remove_all};
+use sigrok_sys::Enum_sr_error_code;
+use sigrok_sys::{sr_resourcepaths_get, Enum_sr_resource_type, sr_log_loglevel_set, sr_log_loglevel_get};
+use glib_sys::{GSList, GVariant, g_main_loop_new, g_main_loop_run};
+use num_rational::Ratio;
+
+#[macro_use]
+mod enums;
+mod trigger;
+mod analog;
+mod logic;
+mod frame;
+mod samplerate;
+#[cfg(feature = "memmap2")]
+mod mmap;
+mod usb;
+mod rational;
+mod metadata;
+mod device_set;
+mod signal_generator;
+mod power_supply;
+mod input;
+mod clock;
+mod value;
+mod error;
+mod consumer;
+mod wav;
+mod mixed;
+mod catalog;
+mod record;
+#[cfg(feature = "toml")]
+mod preset;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub use enums::{TriggerType, Unit, Mq, MqFlag, Function, ConfigKey, ChannelType, ConfigDataType, InstType, LogLevel};
+pub use analog::{Analog, AnalogStats, AnalogEncoding, AnalogValues};
+pub use logic::{Logic, LogicCapture};
+pub use frame::FrameCollector;
+pub use samplerate::SampleRate;
+#[cfg(feature = "memmap2")]
+pub use mmap::MmapCapture;
+pub use usb::UsbAddress;
+pub use rational::{Timebase, Vdiv, ToF64};
+pub use metadata::SessionMetadata;
+pub use device_set::{DeviceId, DeviceSet, DeviceChanges};
+pub use signal_generator::SignalGenerator;
+pub use power_supply::{PowerSupply, ProtectionStatus};
+pub use input::Input;
+pub use clock::CaptureClock;
+pub use value::ConfigValue;
+pub use error::{ConfigError, SrError};
+pub use consumer::DatafeedConsumer;
+pub use wav::{WavWriter, WavFormat};
+pub use mixed::MixedCapture;
+pub use catalog::{DriverCatalog, DriverEntry};
+pub use record::OwnedDatafeed;
+pub use trigger::{Trigger, Triggers, TriggerStage, TriggerBuilder};
 
 #[derive(Debug)]
 pub struct Sigrok {
     context: *mut Struct_sr_context,
+    capabilities: RefCell<Option<Vec<(String, Vec<Function>)>>>,
 }
 
 impl Sigrok {
@@ -27,6 +116,7 @@ impl Sigrok {
         unsafe {
             let mut ctx: Sigrok = Sigrok {
                 context: mem::uninitialized(),
+                capabilities: RefCell::new(None),
             };
             let res = sr_init(&mut ctx.context as *mut _);
             if res == 0 {
@@ -37,6 +127,19 @@ impl Sigrok {
         }
     }
 
+    pub fn builder() -> SigrokBuilder {
+        SigrokBuilder::default()
+    }
+
+    // The log level is global to libsigrok, not per context.
+    pub fn set_log_level(&self, level: LogLevel) -> io::Result<()> {
+        set_log_level(level)
+    }
+
+    pub fn log_level(&self) -> Option<LogLevel> {
+        unsafe { LogLevel::from_raw(sr_log_loglevel_get() as u32) }
+    }
+
     pub fn drivers(&self) -> Vec<Driver> {
         unsafe {
             let mut driver_list: *mut *mut Struct_sr_dev_driver = sr_driver_list(self.context);
@@ -51,14 +154,142 @@ impl Sigrok {
         }
     }
 
+    // The driver list with names cached; see DriverCatalog.
+    pub fn driver_catalog(&self) -> DriverCatalog<'_> {
+        DriverCatalog::new(self)
+    }
+
     pub fn init_driver(&self, driver: &Driver) -> Option<DriverContext> {
         unsafe {
-            let _ = sr_driver_init(self.context, driver.context);
+            if sr_driver_init(self.context, driver.context) != 0 {
+                return None;
+            }
         }
         Some(DriverContext {
             driver: driver.clone()
         })
     }
+
+    // Directories searched for firmware files, in search order. Handy when a
+    // device fails to load its firmware.
+    pub fn resource_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![];
+        unsafe {
+            let list = sr_resourcepaths_get(Enum_sr_resource_type::SR_RESOURCE_FIRMWARE as i32);
+            let mut gslist = list;
+            while !gslist.is_null() {
+                let path = (*gslist).data as *mut os::raw::c_char;
+                paths.push(PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned()));
+                glib_sys::g_free(path as glib_sys::gpointer);
+                gslist = (*gslist).next;
+            }
+            glib_sys::g_slist_free(list);
+        }
+        paths
+    }
+
+    // Loads a session file (.sr) and returns the session, the virtual devices
+    // replaying it and the stored samplerate, if the file has one. A file that
+    // loads without any device is rejected as malformed.
+    pub fn open_session_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<(Session, Vec<DriverInstance>, Option<SampleRate>)> {
+        let session = Session::load(self, path)?;
+        let devices = session.instances();
+        if devices.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Session file contains no devices"));
+        }
+        let samplerate = session.samplerate();
+        Ok((session, devices, samplerate))
+    }
+
+    // See SharedSigrok::global.
+    pub fn global() -> io::Result<SharedSigrok> {
+        SharedSigrok::global()
+    }
+
+    // Initializes every driver once to record its functions. The result is cached,
+    // so later calls don't re-initialize drivers.
+    pub fn driver_capabilities(&self) -> Vec<(String, Vec<Function>)> {
+        let mut cache = self.capabilities.borrow_mut();
+        if cache.is_none() {
+            let mut capabilities = vec![];
+            for driver in self.drivers() {
+                match self.init_driver(&driver) {
+                    Some(ctx) => capabilities.push((driver.name(), ctx.functions())),
+                    None => warn(format_args!("skipping driver {:?}: could not initialize", driver.name())),
+                }
+            }
+            *cache = Some(capabilities);
+        }
+        cache.as_ref().unwrap().clone()
+    }
+}
+
+// Receives warnings about things the crate skipped instead of failing on, e.g.
+// a driver that wouldn't initialize or a malformed packet.
+pub type WarningHandler = Arc<Fn(&str) + Send + Sync>;
+
+static WARNING_HANDLER: Mutex<Option<WarningHandler>> = Mutex::new(None);
+
+// Warnings are dropped until a handler is set, and while libsigrok's log level
+// is below Warn, so they follow the same verbosity as libsigrok's own output.
+pub fn set_warning_handler(handler: Option<WarningHandler>) {
+    *WARNING_HANDLER.lock().unwrap_or_else(|err| err.into_inner()) = handler;
+}
+
+fn warn(message: fmt::Arguments) {
+    let level = unsafe { LogLevel::from_raw(sr_log_loglevel_get() as u32) };
+    // Called outside the lock, so the handler may itself warn or replace the
+    // handler.
+    let handler = WARNING_HANDLER.lock().unwrap_or_else(|err| err.into_inner()).clone();
+    dispatch_warning(level, handler.as_ref().map(|x| &**x as &Fn(&str)), message);
+}
+
+fn dispatch_warning(level: Option<LogLevel>, handler: Option<&Fn(&str)>, message: fmt::Arguments) {
+    if let (Some(level), Some(handler)) = (level, handler) {
+        if level >= LogLevel::Warn {
+            handler(&message.to_string());
+        }
+    }
+}
+
+fn set_log_level(level: LogLevel) -> io::Result<()> {
+    if unsafe { sr_log_loglevel_set(level.to_raw() as os::raw::c_int) } != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Could not set log level {:?}", level)));
+    }
+    Ok(())
+}
+
+// Settings that must be in place before sr_init, applied in order by build().
+#[derive(Debug, Clone, Default)]
+pub struct SigrokBuilder {
+    log_level: Option<LogLevel>,
+    firmware_dir: Option<PathBuf>,
+}
+
+impl SigrokBuilder {
+    // Set first, so messages from sr_init itself already use it.
+    pub fn log_level(mut self, level: LogLevel) -> SigrokBuilder {
+        self.log_level = Some(level);
+        self
+    }
+
+    // Searched before the built-in firmware directories. libsigrok reads it
+    // from the SIGROK_FIRMWARE_DIR environment variable, so this sets that
+    // variable for the whole process.
+    pub fn resource_path<P: AsRef<Path>>(mut self, path: P) -> SigrokBuilder {
+        self.firmware_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build(self) -> io::Result<Sigrok> {
+        if let Some(level) = self.log_level {
+            set_log_level(level)?;
+        }
+        if let Some(ref dir) = self.firmware_dir {
+            env::set_var("SIGROK_FIRMWARE_DIR", dir);
+        }
+        Sigrok::new()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -103,9 +334,47 @@ pub struct DriverContext {
 }
 
 impl DriverContext {
+    pub fn functions(&self) -> Vec<Function> {
+        unsafe { driver_functions(self.driver.context) }
+    }
+
+    // Driver-wide default samplerate, before any device is picked. Many drivers
+    // only answer config requests for a specific device, so this is often None.
+    pub fn samplerate(&self) -> Option<SampleRate> {
+        unsafe {
+            config_get_raw(self.driver.context, ptr::null(), ptr::null(), ConfigKey::SampleRate).map(|gvar| {
+                let value = glib_sys::g_variant_get_uint64(gvar);
+                glib_sys::g_variant_unref(gvar);
+                SampleRate(value)
+            })
+        }
+    }
+
     pub fn scan(&self) -> Vec<DriverInstance> {
+        self.scan_with(&[])
+    }
+
+    pub fn scan_with(&self, options: &[ScanOption]) -> Vec<DriverInstance> {
         unsafe {
-            let gslist = sr_driver_scan(self.driver.context, 0x0 as *mut glib_sys::GSList);
+            let mut list: *mut GSList = ptr::null_mut();
+            for option in options {
+                let config = Box::new(Struct_sr_config {
+                    key: u32::from(option),
+                    data: glib_sys::g_variant_ref_sink(option.to_variant()),
+                });
+                list = glib_sys::g_slist_append(list, Box::into_raw(config) as glib_sys::gpointer);
+            }
+            let gslist = sr_driver_scan(self.driver.context, list);
+
+            // The options stay ours; drivers only read them during the scan.
+            let mut item = list;
+            while !item.is_null() {
+                let config = Box::from_raw((*item).data as *mut Struct_sr_config);
+                glib_sys::g_variant_unref(config.data);
+                item = (*item).next;
+            }
+            glib_sys::g_slist_free(list);
+
             self.enumerate_devices(gslist)
         }
     }
@@ -134,7 +403,7 @@ impl DriverContext {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DriverChannelGroup {
     context: *mut Struct_sr_channel_group,
 }
@@ -145,28 +414,12 @@ impl DriverChannelGroup {
             CStr::from_ptr((*self.context).name).to_string_lossy().into_owned()
         }
     }
-}
-
-#[derive(Debug)]
-pub enum ConfigOption {
-    PatternMode(String),
-    SampleRate(u64),
-}
-
-#[derive(Debug)]
-pub struct DriverInstance {
-    context: *mut Struct_sr_dev_inst,
-}
 
-impl DriverInstance {
     pub fn channels(&self) -> Vec<DriverChannel> {
         let mut channels = vec![];
         unsafe {
-            let mut gslist = sr_dev_inst_channels_get(self.context);
-            loop {
-                if (gslist as usize) == 0x0 {
-                    break;
-                }
+            let mut gslist = (*self.context).channels;
+            while !gslist.is_null() {
                 channels.push(DriverChannel {
                     context: (*gslist).data as *mut Struct_sr_channel,
                 });
@@ -176,243 +429,2104 @@ impl DriverInstance {
         channels
     }
 
-    pub fn channel_groups(&self) -> Vec<DriverChannelGroup> {
-        let mut channels = vec![];
-        unsafe {
-            let mut gslist = sr_dev_inst_channel_groups_get(self.context);
-            loop {
-                if (gslist as usize) == 0x0 {
-                    break;
-                }
-                channels.push(DriverChannelGroup {
-                    context: (*gslist).data as *mut Struct_sr_channel_group,
-                });
-                gslist = (*gslist).next;
-            }
-        }
-        channels
+    pub fn enable_all(&self) -> io::Result<()> {
+        self.set_enabled(true)
     }
 
-    pub fn config_set(&self, config: &ConfigOption) {
-        unsafe {
-            match config {
-                &ConfigOption::PatternMode(ref value) => {
-                    let gvar = glib_sys::g_variant_new_string(CString::new(value.as_bytes()).unwrap().as_ptr());
-                    let res = sr_config_set(self.context, 0 as *const Struct_sr_channel_group, Enum_sr_configkey::SR_CONF_PATTERN_MODE as u32, gvar);
-                    // assert_eq!(res, 0);
-                }
-                &ConfigOption::SampleRate(value) => {
-                    let gvar = glib_sys::g_variant_new_uint64(value);
-                    let res = sr_config_set(self.context, 0 as *const Struct_sr_channel_group, Enum_sr_configkey::SR_CONF_SAMPLERATE as u32, gvar);
-                    // assert_eq!(res, 0);
-                }
-            }
-        }
+    pub fn disable_all(&self) -> io::Result<()> {
+        self.set_enabled(false)
     }
 
-    pub fn config_set_channel_group(&self, group: &DriverChannelGroup, config: &ConfigOption) {
-        unsafe {
-            match config {
-                &ConfigOption::PatternMode(ref value) => {
-                    let gvar = glib_sys::g_variant_new_string(CString::new(value.as_bytes()).unwrap().as_ptr());
-                    let res = sr_config_set(self.context, group.context, Enum_sr_configkey::SR_CONF_PATTERN_MODE as u32, gvar);
-                    // assert_eq!(res, 0);
-                }
-                &ConfigOption::SampleRate(value) => {
-                    let gvar = glib_sys::g_variant_new_uint64(value);
-                    let res = sr_config_set(self.context, group.context, Enum_sr_configkey::SR_CONF_SAMPLERATE as u32, gvar);
-                    // assert_eq!(res, 0);
-                }
+    // libsigrok has no batch call, so each channel is set individually, skipping
+    // those already in the requested state. Every channel is attempted; failures
+    // are reported together.
+    fn set_enabled(&self, enabled: bool) -> io::Result<()> {
+        let mut failed = vec![];
+        for channel in self.channels() {
+            if channel.is_enabled() == enabled {
+                continue;
             }
+            if channel.set_enabled(enabled).is_err() {
+                failed.push(channel.name());
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, format!("Could not {} channels: {}",
+                                                             if enabled { "enable" } else { "disable" },
+                                                             failed.join(", "))))
         }
     }
 
-    // pub fn output(&self, output: &Output) {
-    //     unsafe {
-    //         let output = sr_output_new(output.context, 0x0 as *mut glib_sys::GHashTable, self.context, 0x0 as *const i8);
-    //
-    //     }
-    // }
+    // Channel groups don't point back at their device; go through a member channel.
+    fn device(&self) -> io::Result<DriverInstance> {
+        self.channels()
+            .get(0)
+            .map(|x| x.device())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Channel group has no channels"))
+    }
+
+    // Probe attenuation (1 for 1x, 10 for 10x). Drivers that support this key
+    // either forward it to the instrument or fold it into their own scaling, so
+    // analog values they report are already probe-corrected.
+    pub fn set_probe_factor(&self, factor: u64) -> io::Result<()> {
+        self.device()?.config_apply(Some(self), &ConfigOption::ProbeFactor(factor))
+    }
+
+    pub fn probe_factor(&self) -> Option<u64> {
+        self.device().ok()?.config_get_u64(Some(self), ConfigKey::ProbeFactor)
+    }
 }
 
-#[derive(Debug)]
-pub struct DriverChannel {
-    context: *mut Struct_sr_channel,
+// Options passed to a driver while scanning. Raw covers driver-specific keys
+// that aren't modeled here.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanOption {
+    Connection(String),
+    SerialComm(String),
+    ModbusAddr(u64),
+    Raw {
+        key: u32,
+        value: RawScanValue,
+    },
 }
 
-impl DriverChannel {
-    pub fn index(&self) -> u32 {
-        unsafe {
-            (*self.context).index as u32
-        }
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawScanValue {
+    String(String),
+    U64(u64),
+    Bool(bool),
+}
 
-    pub fn name(&self) -> String {
-        unsafe {
-            CStr::from_ptr((*self.context).name).to_string_lossy().into_owned()
+impl<'a> From<&'a ScanOption> for u32 {
+    fn from(option: &'a ScanOption) -> u32 {
+        match option {
+            &ScanOption::Connection(_) => Enum_sr_configkey::SR_CONF_CONN as u32,
+            &ScanOption::SerialComm(_) => Enum_sr_configkey::SR_CONF_SERIALCOMM as u32,
+            &ScanOption::ModbusAddr(_) => Enum_sr_configkey::SR_CONF_MODBUSADDR as u32,
+            &ScanOption::Raw { key, .. } => key,
         }
     }
+}
 
-    pub fn disable(&self) {
-        unsafe {
-            let _ = sr_dev_channel_enable(self.context, 0);
-            // println!("disabling: {:?}", res);
+impl ScanOption {
+    unsafe fn to_variant(&self) -> *mut GVariant {
+        match self {
+            &ScanOption::Connection(ref value) |
+            &ScanOption::SerialComm(ref value) |
+            &ScanOption::Raw { value: RawScanValue::String(ref value), .. } => {
+                glib_sys::g_variant_new_string(CString::new(value.as_bytes()).unwrap().as_ptr())
+            }
+            &ScanOption::ModbusAddr(value) |
+            &ScanOption::Raw { value: RawScanValue::U64(value), .. } => {
+                glib_sys::g_variant_new_uint64(value)
+            }
+            &ScanOption::Raw { value: RawScanValue::Bool(value), .. } => {
+                glib_sys::g_variant_new_boolean(value as glib_sys::gboolean)
+            }
         }
     }
+}
 
-    pub fn enable(&self) {
-        unsafe {
-            let _ = sr_dev_channel_enable(self.context, 1);
-            // println!("enabling: {:?}", res);
+#[derive(Debug)]
+pub enum ConfigOption {
+    PatternMode(String),
+    SampleRate(u64),
+    Timebase(Ratio<u64>),
+    Vdiv(Ratio<u64>),
+    HorizTriggerPos(f64),
+    SampleInterval(u64),
+    Rle(bool),
+    LogicThreshold(String),
+    LogicThresholdCustom(f64),
+    MeasuredQuantity(Mq, Vec<MqFlag>),
+    ProbeFactor(u64),
+    OutputFrequency(f64),
+    OutputFrequencyTarget(f64),
+    Amplitude(f64),
+    Offset(f64),
+    DeviceMode(String),
+    VoltageTarget(f64),
+    CurrentLimit(f64),
+    Enabled(bool),
+    OverVoltageProtectionEnabled(bool),
+    OverVoltageProtectionThreshold(f64),
+    OverCurrentProtectionEnabled(bool),
+    OverCurrentProtectionThreshold(f64),
+    LimitSamples(u64),
+    CenterFrequency(u64),
+    BufferSize(u64),
+    LimitMsec(u64),
+    ExternalClock(bool),
+    ExternalClockSource(String),
+    ClockEdge(String),
+    LimitFrames(u64),
+    AdcPowerlineCycles(f64),
+}
+
+impl ConfigOption {
+    pub fn key(&self) -> ConfigKey {
+        match self {
+            &ConfigOption::PatternMode(_) => ConfigKey::PatternMode,
+            &ConfigOption::SampleRate(_) => ConfigKey::SampleRate,
+            &ConfigOption::Timebase(_) => ConfigKey::Timebase,
+            &ConfigOption::Vdiv(_) => ConfigKey::Vdiv,
+            &ConfigOption::HorizTriggerPos(_) => ConfigKey::HorizTriggerPos,
+            &ConfigOption::SampleInterval(_) => ConfigKey::SampleInterval,
+            &ConfigOption::Rle(_) => ConfigKey::Rle,
+            &ConfigOption::LogicThreshold(_) => ConfigKey::LogicThreshold,
+            &ConfigOption::LogicThresholdCustom(_) => ConfigKey::LogicThresholdCustom,
+            &ConfigOption::MeasuredQuantity(..) => ConfigKey::MeasuredQuantity,
+            &ConfigOption::ProbeFactor(_) => ConfigKey::ProbeFactor,
+            &ConfigOption::OutputFrequency(_) => ConfigKey::OutputFrequency,
+            &ConfigOption::OutputFrequencyTarget(_) => ConfigKey::OutputFrequencyTarget,
+            &ConfigOption::Amplitude(_) => ConfigKey::Amplitude,
+            &ConfigOption::Offset(_) => ConfigKey::Offset,
+            &ConfigOption::DeviceMode(_) => ConfigKey::DeviceMode,
+            &ConfigOption::VoltageTarget(_) => ConfigKey::VoltageTarget,
+            &ConfigOption::CurrentLimit(_) => ConfigKey::CurrentLimit,
+            &ConfigOption::Enabled(_) => ConfigKey::Enabled,
+            &ConfigOption::OverVoltageProtectionEnabled(_) => ConfigKey::OverVoltageProtectionEnabled,
+            &ConfigOption::OverVoltageProtectionThreshold(_) => ConfigKey::OverVoltageProtectionThreshold,
+            &ConfigOption::OverCurrentProtectionEnabled(_) => ConfigKey::OverCurrentProtectionEnabled,
+            &ConfigOption::OverCurrentProtectionThreshold(_) => ConfigKey::OverCurrentProtectionThreshold,
+            &ConfigOption::LimitSamples(_) => ConfigKey::LimitSamples,
+            &ConfigOption::CenterFrequency(_) => ConfigKey::CenterFrequency,
+            &ConfigOption::BufferSize(_) => ConfigKey::BufferSize,
+            &ConfigOption::LimitMsec(_) => ConfigKey::LimitMsec,
+            &ConfigOption::ExternalClock(_) => ConfigKey::ExternalClock,
+            &ConfigOption::ExternalClockSource(_) => ConfigKey::ExternalClockSource,
+            &ConfigOption::ClockEdge(_) => ConfigKey::ClockEdge,
+            &ConfigOption::LimitFrames(_) => ConfigKey::LimitFrames,
+            &ConfigOption::AdcPowerlineCycles(_) => ConfigKey::AdcPowerlineCycles,
         }
     }
-}
 
-impl Drop for Sigrok {
-    fn drop(&mut self) {
-        unsafe {
-            let res = sr_exit(self.context);
-            if res == 0 {
-                // noop
-            } else {
-                panic!("Failed on sigrok context destructor")
+    unsafe fn to_variant(&self) -> *mut GVariant {
+        match self {
+            &ConfigOption::PatternMode(ref value) | &ConfigOption::LogicThreshold(ref value) |
+            &ConfigOption::DeviceMode(ref value) | &ConfigOption::ExternalClockSource(ref value) |
+            &ConfigOption::ClockEdge(ref value) => {
+                glib_sys::g_variant_new_string(CString::new(value.as_bytes()).unwrap().as_ptr())
+            }
+            &ConfigOption::SampleRate(value) | &ConfigOption::SampleInterval(value) |
+            &ConfigOption::ProbeFactor(value) | &ConfigOption::LimitSamples(value) |
+            &ConfigOption::CenterFrequency(value) | &ConfigOption::BufferSize(value) |
+            &ConfigOption::LimitMsec(value) | &ConfigOption::LimitFrames(value) => {
+                glib_sys::g_variant_new_uint64(value)
+            }
+            &ConfigOption::Timebase(ref value) | &ConfigOption::Vdiv(ref value) => {
+                rational_variant(value)
+            }
+            &ConfigOption::HorizTriggerPos(value) | &ConfigOption::LogicThresholdCustom(value) |
+            &ConfigOption::OutputFrequency(value) | &ConfigOption::OutputFrequencyTarget(value) |
+            &ConfigOption::Amplitude(value) | &ConfigOption::Offset(value) |
+            &ConfigOption::VoltageTarget(value) | &ConfigOption::CurrentLimit(value) |
+            &ConfigOption::OverVoltageProtectionThreshold(value) |
+            &ConfigOption::OverCurrentProtectionThreshold(value) |
+            &ConfigOption::AdcPowerlineCycles(value) => {
+                glib_sys::g_variant_new_double(value)
+            }
+            &ConfigOption::Rle(value) | &ConfigOption::Enabled(value) | &ConfigOption::ExternalClock(value) |
+            &ConfigOption::OverVoltageProtectionEnabled(value) |
+            &ConfigOption::OverCurrentProtectionEnabled(value) => {
+                glib_sys::g_variant_new_boolean(value as glib_sys::gboolean)
+            }
+            &ConfigOption::MeasuredQuantity(mq, ref flags) => {
+                let flags = flags.iter().fold(0u64, |acc, x| acc | x.to_raw() as u64);
+                let mut children = [
+                    glib_sys::g_variant_new_uint32(mq.to_raw()),
+                    glib_sys::g_variant_new_uint64(flags),
+                ];
+                glib_sys::g_variant_new_tuple(children.as_mut_ptr(), children.len())
             }
         }
     }
 }
 
-pub struct Session {
-    context: *mut Struct_sr_session,
-    _callbacks: Vec<Box<SessionCallback>>,
+unsafe fn driver_functions(driver: *const Struct_sr_dev_driver) -> Vec<Function> {
+    if driver.is_null() {
+        return vec![];
+    }
+    let options = sr_dev_options(driver, ptr::null(), ptr::null());
+    if options.is_null() {
+        return vec![];
+    }
+    let keys = slice::from_raw_parts((*options).data as *const u32, (*options).len as usize);
+    let functions = keys.iter().filter_map(|&key| Function::from_raw(key)).collect();
+    glib_sys::g_array_free(options, 1);
+    functions
 }
 
-pub enum Datafeed<'a> {
-    Header {
-        feed_version: i32,
-        start_time: time::Timespec,
-    },
-    Logic {
-        unit_size: u32,
-        data: &'a [u8],
+unsafe fn config_get_raw(driver: *const Struct_sr_dev_driver, sdi: *const Struct_sr_dev_inst,
+                         group: *const Struct_sr_channel_group, key: ConfigKey) -> Option<*mut GVariant> {
+    let mut gvar: *mut GVariant = ptr::null_mut();
+    let res = sr_config_get(driver, sdi, group, key.to_raw(), &mut gvar as *mut _);
+    if res == 0 && !gvar.is_null() {
+        Some(gvar)
+    } else {
+        None
     }
 }
 
-unsafe extern "C" fn sr_session_callback(inst: *const Struct_sr_dev_inst, packet: *const Struct_sr_datafeed_packet, data: *mut os::raw::c_void) {
-    // See session.c in sigrok-cli line 186
-    let kind = (*packet)._type;
-
-    let cb: &mut Box<SessionCallback> = mem::transmute(data);
-    let driver = DriverInstance {
-        context: inst as *mut _,
-    };
+// Rational config values are (p, q) tuples of uint64 ("(tt)"), not ranges.
+unsafe fn rational_variant(value: &Ratio<u64>) -> *mut GVariant {
+    let mut children = [
+        glib_sys::g_variant_new_uint64(*value.numer()),
+        glib_sys::g_variant_new_uint64(*value.denom()),
+    ];
+    glib_sys::g_variant_new_tuple(children.as_mut_ptr(), children.len())
+}
 
-    if kind == (Enum_sr_packettype::SR_DF_HEADER as u16) {
-        let header: *const Struct_sr_datafeed_header = (*packet).payload as usize as *const _;
+unsafe fn variant_type(gvar: *mut GVariant) -> String {
+    CStr::from_ptr(glib_sys::g_variant_get_type_string(gvar)).to_string_lossy().into_owned()
+}
 
-        cb(&driver, &Datafeed::Header {
-            feed_version: (*header).feed_version as i32,
-            start_time: time::Timespec {
-                sec: (*header).starttime.tv_sec as i64,
-                nsec: ((*header).starttime.tv_usec as i32) * 1000,
-            },
-        });
-    } else if kind == (Enum_sr_packettype::SR_DF_LOGIC as u16) {
-        let logic: *const Struct_sr_datafeed_logic = (*packet).payload as usize as *const _;
-        let parts = slice::from_raw_parts::<u8>((*logic).data as usize as *const _, (*logic).length as usize);
+// Consumes the variant.
+unsafe fn owned_variant_type(gvar: *mut GVariant) -> String {
+    let signature = variant_type(gvar);
+    glib_sys::g_variant_unref(gvar);
+    signature
+}
 
-        cb(&driver, &Datafeed::Logic {
-            unit_size: (*logic).unitsize as u32,
-            data: parts,
-        });
-    } else if kind == (Enum_sr_packettype::SR_DF_ANALOG as u16) {
-        // let analog: *const Struct_sr_datafeed_analog = (*packet).payload as usize as *const _;
-        // println!("TODO: analog");
-        // pub data: *mut ::std::os::raw::c_void,
-        // pub num_samples: uint32_t,
-        // pub encoding: *mut Struct_sr_analog_encoding,
-        // pub meaning: *mut Struct_sr_analog_meaning,
-        // pub spec: *mut Struct_sr_analog_spec,
-    } else if kind == (Enum_sr_packettype::SR_DF_END as u16) {
-        println!("TODO: end");
-    } else if kind == (Enum_sr_packettype::SR_DF_META as u16) {
-        println!("TODO: meta");
-    } else if kind == (Enum_sr_packettype::SR_DF_TRIGGER as u16) {
-        println!("TODO: trigger");
-    } else if kind == (Enum_sr_packettype::SR_DF_ANALOG_OLD as u16) {
-        println!("TODO: analog old");
-    } else if kind == (Enum_sr_packettype::SR_DF_FRAME_BEGIN as u16) {
-        println!("TODO: frame begin");
-    } else if kind == (Enum_sr_packettype::SR_DF_FRAME_END as u16) {
-        println!("TODO: frame end");
+unsafe fn variant_strings(gvar: *mut GVariant) -> Vec<String> {
+    let mut values = vec![];
+    for i in 0..glib_sys::g_variant_n_children(gvar) {
+        let child = glib_sys::g_variant_get_child_value(gvar, i);
+        values.push(CStr::from_ptr(glib_sys::g_variant_get_string(child, ptr::null_mut())).to_string_lossy().into_owned());
+        glib_sys::g_variant_unref(child);
     }
+    values
 }
 
-pub type SessionCallback = FnMut(&DriverInstance, &Datafeed);
-
-impl Session {
-    pub fn new(ctx: &mut Sigrok) -> Option<Session> {
-        unsafe {
-            let mut session = Session {
-                context: mem::uninitialized(),
-                _callbacks: vec![],
-            };
-            if sr_session_new(ctx.context, &mut session.context as *mut _) == 0x0 {
-                Some(session)
-            } else {
-                None
+// Decodes the shapes libsigrok uses to list a float range: "(ddd)" min/max/step,
+// "(dd)" min/max, "a(dd)" several ranges (merged) or "ad" discrete values.
+unsafe fn variant_f64_range(gvar: *mut GVariant) -> Option<(f64, f64)> {
+    let child_f64 = |gvar: *mut GVariant, i: usize| {
+        let child = glib_sys::g_variant_get_child_value(gvar, i);
+        let value = glib_sys::g_variant_get_double(child);
+        glib_sys::g_variant_unref(child);
+        value
+    };
+    let merge = |range: Option<(f64, f64)>, (l, h): (f64, f64)| match range {
+        Some((min, max)) => Some((min.min(l), max.max(h))),
+        None => Some((l, h)),
+    };
+    match &variant_type(gvar)[..] {
+        "(ddd)" | "(dd)" => Some((child_f64(gvar, 0), child_f64(gvar, 1))),
+        "a(dd)" => {
+            let mut range = None;
+            for i in 0..glib_sys::g_variant_n_children(gvar) {
+                let tuple = glib_sys::g_variant_get_child_value(gvar, i);
+                range = merge(range, (child_f64(tuple, 0), child_f64(tuple, 1)));
+                glib_sys::g_variant_unref(tuple);
             }
+            range
         }
-    }
-
-    pub fn callback_add(&mut self, callback: Box<SessionCallback>) {
-        unsafe {
-            self._callbacks.push(callback);
-            let _ = sr_session_datafeed_callback_add(self.context, Some(sr_session_callback), mem::transmute(&self._callbacks[self._callbacks.len() - 1]));
+        "ad" => {
+            let mut range = None;
+            for i in 0..glib_sys::g_variant_n_children(gvar) {
+                let value = child_f64(gvar, i);
+                range = merge(range, (value, value));
+            }
+            range
         }
+        _ => None,
     }
+}
 
-    pub fn add_instance(&self, instance: &DriverInstance) {
-        unsafe {
-            let _ = sr_dev_open(instance.context);
-            let _ = sr_session_dev_add(self.context, instance.context);
+// Integer counterpart of variant_f64_range: "(ttt)" or "(tt)" min/max(/step),
+// or "at" discrete values.
+unsafe fn variant_u64_range(gvar: *mut GVariant) -> Option<(u64, u64)> {
+    let child_u64 = |gvar: *mut GVariant, i: usize| {
+        let child = glib_sys::g_variant_get_child_value(gvar, i);
+        let value = glib_sys::g_variant_get_uint64(child);
+        glib_sys::g_variant_unref(child);
+        value
+    };
+    match &variant_type(gvar)[..] {
+        "(ttt)" | "(tt)" => Some((child_u64(gvar, 0), child_u64(gvar, 1))),
+        "at" => {
+            let values = (0..glib_sys::g_variant_n_children(gvar)).map(|i| child_u64(gvar, i)).collect::<Vec<_>>();
+            match (values.iter().min(), values.iter().max()) {
+                (Some(&min), Some(&max)) => Some((min, max)),
+                _ => None,
+            }
         }
+        _ => None,
     }
+}
 
-    pub fn start(&self) {
-        unsafe {
-            sr_session_start(self.context);
-        }
+// Decodes an "a(tt)" list as returned by sr_config_list for SR_CONF_TIMEBASE / SR_CONF_VDIV.
+unsafe fn variant_rationals(gvar: *mut GVariant) -> Vec<Ratio<u64>> {
+    let mut values = vec![];
+    for i in 0..glib_sys::g_variant_n_children(gvar) {
+        let tuple = glib_sys::g_variant_get_child_value(gvar, i);
+        let p = glib_sys::g_variant_get_child_value(tuple, 0);
+        let q = glib_sys::g_variant_get_child_value(tuple, 1);
+        values.push(Ratio::new_raw(glib_sys::g_variant_get_uint64(p), glib_sys::g_variant_get_uint64(q)));
+        glib_sys::g_variant_unref(p);
+        glib_sys::g_variant_unref(q);
+        glib_sys::g_variant_unref(tuple);
     }
+    values
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConfigAbilities {
+    pub get: bool,
+    pub set: bool,
+    pub list: bool,
+}
 
-pub fn main_loop() {
-    unsafe {
-        let main_loop = g_main_loop_new(0x0 as *mut _, 0);
-        g_main_loop_run(main_loop);
-    }
+// Outcome of DriverInstance::clone_config_to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigTransfer {
+    pub transferred: Vec<ConfigKey>,
+    pub skipped: Vec<ConfigKey>,
 }
 
-#[cfg(test)]
-fn it_works_datafeed(_: &DriverInstance, data: &Datafeed) {
-    match data {
-        &Datafeed::Logic { unit_size, data } => {
-            let _ = unit_size;
-            for i in 0..64 {
-                println!("{}", format!("{:08b}", data[i]).replace("1", ".").replace("0", "X"));
-            }
-            println!("");
-            ::std::process::exit(0);
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicThresholdLevel {
+    // One of the device's predefined levels, e.g. "TTL" or "1.8V".
+    Named(String),
+    // A custom threshold in volts.
+    Custom(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEdge {
+    Rising,
+    Falling,
+}
+
+impl ClockEdge {
+    // Drivers name edges differently ("rising", "r", ...), so only the first
+    // letter is significant.
+    fn from_name(name: &str) -> Option<ClockEdge> {
+        match name.chars().next().map(|x| x.to_ascii_lowercase()) {
+            Some('r') => Some(ClockEdge::Rising),
+            Some('f') => Some(ClockEdge::Falling),
+            _ => None,
         }
-        _ => { }
     }
 }
 
-#[test]
-fn it_works() {
-    // Print out available drivers.
-    let mut ctx = Sigrok::new().unwrap();
-    for driver in ctx.drivers() {
+// External clocking for devices sampling on a clock input. Either field can be
+// None for devices without that choice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalClockConfig {
+    pub source: Option<String>,
+    pub edge: Option<ClockEdge>,
+}
+
+fn is_custom_threshold(name: &str) -> bool {
+    name.eq_ignore_ascii_case("user") || name.eq_ignore_ascii_case("custom")
+}
+
+// Leading fields of libsigrok's internal struct sr_dev_inst, which have kept this
+// layout since 0.4.
+#[repr(C)]
+struct DevInstHead {
+    driver: *mut Struct_sr_dev_driver,
+    status: os::raw::c_int,
+    inst_type: os::raw::c_int,
+}
+
+// Instances compare and hash by identity: two values are equal when they wrap
+// the same libsigrok device.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DriverInstance {
+    context: *mut Struct_sr_dev_inst,
+}
+
+unsafe fn optional_string(value: *const os::raw::c_char) -> Option<String> {
+    if value.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+    }
+}
+
+impl DriverInstance {
+    // Creates a device that isn't backed by a driver, e.g. to feed output modules.
+    // libsigrok has no setters for these labels, so they're fixed at creation.
+    pub fn new_user(vendor: &str, model: &str, version: &str) -> io::Result<DriverInstance> {
+        let vendor = CString::new(vendor)?;
+        let model = CString::new(model)?;
+        let version = CString::new(version)?;
+        unsafe {
+            let context = sr_dev_inst_user_new(vendor.as_ptr(), model.as_ptr(), version.as_ptr());
+            if context.is_null() {
+                return Err(io::Error::new(io::ErrorKind::Other, "Could not create user device"));
+            }
+            Ok(DriverInstance {
+                context: context,
+            })
+        }
+    }
+
+    pub fn is_user(&self) -> bool {
+        unsafe {
+            sr_dev_inst_driver_get(self.context).is_null()
+        }
+    }
+
+    // Captures `samples` logic samples through `session` and returns them. The
+    // device is attached to the session if it isn't already, and the call blocks
+    // until acquisition ends.
+    pub fn capture_logic(&self, session: &mut Session, samples: u64) -> io::Result<LogicCapture> {
+        self.config_apply(None, &ConfigOption::LimitSamples(samples))?;
+        if !session.has_instance(self) {
+            session.add_instance(self);
+        }
+
+        let capture = Rc::new(RefCell::new(LogicCapture::default()));
+        let sink = capture.clone();
+        let device = self.context;
+        session.run_once(Box::new(move |instance, data| {
+            if let &Datafeed::Logic(ref logic) = data {
+                if instance.context == device {
+                    sink.borrow_mut().push(logic);
+                }
+            }
+        }))?;
+
+        let capture = capture.borrow().clone();
+        Ok(capture)
+    }
+
+    // Captures `samples` samples of every enabled channel, logic and analog,
+    // through `session`. Blocks until acquisition ends, like capture_logic.
+    pub fn capture_mixed(&self, session: &mut Session, samples: u64) -> io::Result<MixedCapture> {
+        self.config_apply(None, &ConfigOption::LimitSamples(samples))?;
+        if !session.has_instance(self) {
+            session.add_instance(self);
+        }
+
+        let mut capture = MixedCapture::new(self.samplerate().map(|x| x.hz()).unwrap_or(0));
+        let mut error = None;
+        let device = self.context;
+        session.start_with_callback(None, &mut |instance, data| {
+            if instance.context == device && error.is_none() {
+                if let Err(err) = capture.push(data) {
+                    error = Some(err);
+                }
+            }
+        })?;
+        match error {
+            Some(err) => Err(err),
+            None => Ok(capture),
+        }
+    }
+
+    // Stops acquisition after `frames` frames. Only oscilloscopes capture in
+    // frames.
+    pub fn set_frame_limit(&self, frames: u64) -> io::Result<()> {
+        if !self.functions().contains(&Function::Oscilloscope) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Device doesn't capture frames"));
+        }
+        self.config_set_checked(&ConfigOption::LimitFrames(frames))
+    }
+
+    // Captures `frames` frames through `session` and returns the ones that
+    // completed, each as per-channel samples (see FrameCollector::channels).
+    // Blocks until acquisition ends, like capture_logic.
+    pub fn capture_frames(&self, session: &mut Session, frames: u64) -> io::Result<Vec<Vec<(String, Vec<f32>)>>> {
+        self.set_frame_limit(frames)?;
+        if !session.has_instance(self) {
+            session.add_instance(self);
+        }
+
+        let mut collector = FrameCollector::new();
+        let mut captured = vec![];
+        let mut error = None;
+        let device = self.context;
+        session.start_with_callback(None, &mut |instance, data| {
+            if instance.context != device || error.is_some() {
+                return;
+            }
+            match collector.push(data) {
+                Ok(true) => {
+                    if let &Datafeed::FrameEnd = data {
+                        captured.push(collector.channels().to_vec());
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => error = Some(err),
+            }
+        })?;
+        match error {
+            Some(err) => Err(err),
+            None => Ok(captured),
+        }
+    }
+
+    // Switches a multi-function instrument through SR_CONF_DEVICE_MODE, picking
+    // the driver's mode name that corresponds to `function`.
+    pub fn set_mode(&self, function: Function) -> io::Result<()> {
+        if !self.functions().contains(&function) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Device doesn't support {:?}", function)));
+        }
+        let mode = self.config_string_options(ConfigKey::DeviceMode)?
+            .into_iter()
+            .find(|x| function.matches_mode(x))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No device mode for {:?}", function)))?;
+        self.config_apply(None, &ConfigOption::DeviceMode(mode))
+    }
+
+    pub fn current_mode(&self) -> io::Result<Function> {
+        let mode = self.config_get_string(None, ConfigKey::DeviceMode)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Device has no device mode"))?;
+        self.functions()
+            .into_iter()
+            .find(|x| x.matches_mode(&mode))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Unrecognized device mode {:?}", mode)))
+    }
+
+    // Selected meter resolution, e.g. "4.5". See Analog::display_digits for the
+    // precision of actual readings.
+    pub fn digits(&self) -> Option<String> {
+        self.config_get_string(None, ConfigKey::Digits)
+    }
+
+    // Transport the device was found on. libsigrok has no getter for this, so it's
+    // read from the leading fields of the (otherwise opaque) instance struct.
+    pub fn inst_type(&self) -> Option<InstType> {
+        unsafe {
+            InstType::from_raw((*(self.context as *const DevInstHead)).inst_type as u32)
+        }
+    }
+
+    // Only user-created devices can have channels added.
+    pub fn add_channel(&self, index: u32, kind: ChannelType, name: &str) -> io::Result<()> {
+        if !self.is_user() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Channels can only be added to user devices"));
+        }
+        let name = CString::new(name)?;
+        unsafe {
+            if sr_dev_inst_channel_add(self.context, index as i32, kind.to_raw() as i32, name.as_ptr()) != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Could not add channel"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn vendor(&self) -> Option<String> {
+        unsafe { optional_string(sr_dev_inst_vendor_get(self.context)) }
+    }
+
+    pub fn model(&self) -> Option<String> {
+        unsafe { optional_string(sr_dev_inst_model_get(self.context)) }
+    }
+
+    pub fn version(&self) -> Option<String> {
+        unsafe { optional_string(sr_dev_inst_version_get(self.context)) }
+    }
+
+    pub fn serial_number(&self) -> Option<String> {
+        unsafe { optional_string(sr_dev_inst_sernum_get(self.context)) }
+    }
+
+    pub fn conn_id(&self) -> Option<String> {
+        unsafe { optional_string(sr_dev_inst_connid_get(self.context)) }
+    }
+
+    // Functions of the device's driver; empty for user-created devices.
+    pub fn functions(&self) -> Vec<Function> {
+        unsafe { driver_functions(sr_dev_inst_driver_get(self.context)) }
+    }
+
+    pub fn id(&self) -> DeviceId {
+        let driver = unsafe { sr_dev_inst_driver_get(self.context) };
+        DeviceId {
+            driver: if driver.is_null() {
+                None
+            } else {
+                Some(Driver { context: driver as *mut _ }.name())
+            },
+            vendor: self.vendor(),
+            model: self.model(),
+            serial_number: self.serial_number(),
+            conn_id: self.conn_id(),
+        }
+    }
+
+    // See DeviceId::stable_id for what this is stable across.
+    pub fn stable_id(&self) -> String {
+        self.id().stable_id()
+    }
+
+    // Only connection ids in one of the UsbAddress forms parse; USB devices that
+    // report a physical port path (e.g. "1-1.4") return None.
+    pub fn usb_address(&self) -> Option<UsbAddress> {
+        self.conn_id().and_then(|x| UsbAddress::parse(&x))
+    }
+
+    pub fn channels(&self) -> Vec<DriverChannel> {
+        let mut channels = vec![];
+        unsafe {
+            let mut gslist = sr_dev_inst_channels_get(self.context);
+            loop {
+                if (gslist as usize) == 0x0 {
+                    break;
+                }
+                channels.push(DriverChannel {
+                    context: (*gslist).data as *mut Struct_sr_channel,
+                });
+                gslist = (*gslist).next;
+            }
+        }
+        channels
+    }
+
+    // Counts channels without wrapping them. The list is filled in at scan
+    // time, so this works before the device is opened.
+    pub fn total_channels(&self) -> usize {
+        unsafe { glib_sys::g_slist_length(sr_dev_inst_channels_get(self.context)) as usize }
+    }
+
+    pub fn channel_by_index(&self, index: u32) -> Option<DriverChannel> {
+        self.channels().into_iter().find(|x| x.index() == index)
+    }
+
+    pub fn channel_by_name(&self, name: &str) -> Option<DriverChannel> {
+        self.channels().into_iter().find(|x| x.name() == name)
+    }
+
+    // Bit position of each enabled logic channel within a logic sample. libsigrok
+    // packs logic data by channel index (as its output modules do), so disabled
+    // channels leave their bit unused rather than shifting later channels down.
+    pub fn channel_bit_map(&self) -> Vec<(DriverChannel, usize)> {
+        let mut map: Vec<(DriverChannel, usize)> = self.channels()
+            .into_iter()
+            .filter(|x| x.is_logic() && x.is_enabled())
+            .map(|x| {
+                let bit = x.index() as usize;
+                (x, bit)
+            })
+            .collect();
+        map.sort_by_key(|x| x.1);
+        map
+    }
+
+    // Order of the channels in a multi-channel Analog packet: the enabled analog
+    // channels by index, which is how drivers fill in the packet's channel list.
+    // Packets should still be decoded by their own `channels`, since drivers that
+    // send one packet per channel (most meters and supplies) don't interleave.
+    pub fn analog_channel_layout(&self) -> Vec<DriverChannel> {
+        let mut channels: Vec<DriverChannel> = self.channels()
+            .into_iter()
+            .filter(|x| x.is_analog() && x.is_enabled())
+            .collect();
+        channels.sort_by_key(|x| x.index());
+        channels
+    }
+
+    pub fn channel_groups(&self) -> Vec<DriverChannelGroup> {
+        let mut channels = vec![];
+        unsafe {
+            let mut gslist = sr_dev_inst_channel_groups_get(self.context);
+            loop {
+                if (gslist as usize) == 0x0 {
+                    break;
+                }
+                channels.push(DriverChannelGroup {
+                    context: (*gslist).data as *mut Struct_sr_channel_group,
+                });
+                gslist = (*gslist).next;
+            }
+        }
+        channels
+    }
+
+    pub fn config_set(&self, config: &ConfigOption) {
+        let _ = self.config_apply(None, config);
+    }
+
+    pub fn config_set_channel_group(&self, group: &DriverChannelGroup, config: &ConfigOption) {
+        let _ = self.config_apply(Some(group), config);
+    }
+
+    // Applies `config` then sleeps for `settle`, e.g. after switching a meter's
+    // range so the next reading isn't stale. libsigrok doesn't report settling
+    // times, so the delay is up to the caller. Nothing is waited on failure.
+    pub fn config_apply_and_settle(&self, group: Option<&DriverChannelGroup>, config: &ConfigOption,
+                                   settle: Duration) -> io::Result<()> {
+        self.config_apply(group, config)?;
+        thread::sleep(settle);
+        Ok(())
+    }
+
+    pub fn config_abilities(&self, key: ConfigKey) -> ConfigAbilities {
+        self.abilities(None, key)
+    }
+
+    // Like config_abilities, for a raw SR_CONF_* key, e.g. one this crate has no
+    // ConfigKey for. Unknown keys report no abilities.
+    pub fn config_abilities_raw(&self, key: u32) -> ConfigAbilities {
+        self.abilities_raw(None, key)
+    }
+
+    // Every key the device supports with its abilities, sorted by the key's
+    // numeric value (libsigrok's own grouping: device options, then acquisition
+    // limits, ...). The order sr_dev_options reports varies between drivers, so
+    // it's not kept. Keys this crate doesn't know are left out.
+    pub fn config_options(&self) -> Vec<(ConfigKey, ConfigAbilities)> {
+        let mut keys: Vec<ConfigKey> = unsafe {
+            let options = sr_dev_options(sr_dev_inst_driver_get(self.context), self.context, ptr::null());
+            if options.is_null() {
+                return vec![];
+            }
+            let caps = Enum_sr_configcap::SR_CONF_GET as u32 | Enum_sr_configcap::SR_CONF_SET as u32 |
+                Enum_sr_configcap::SR_CONF_LIST as u32;
+            let raw = slice::from_raw_parts((*options).data as *const u32, (*options).len as usize);
+            let keys = raw.iter().filter_map(|&key| ConfigKey::from_raw(key & !caps)).collect();
+            glib_sys::g_array_free(options, 1);
+            keys
+        };
+        keys.sort_by_key(|x| x.to_raw());
+        keys.dedup();
+        keys.into_iter().map(|x| (x, self.abilities(None, x))).collect()
+    }
+
+    // Current values of every key that can be both read and written.
+    fn config_snapshot(&self) -> Vec<(ConfigKey, ConfigValue)> {
+        self.config_options().into_iter()
+            .filter(|&(_, abilities)| abilities.get && abilities.set)
+            .filter_map(|(key, _)| self.config_get_value(key).ok().map(|value| (key, value)))
+            .collect()
+    }
+
+    // Sets every settable key back to the driver's defaults. libsigrok 0.5 has
+    // no reset config key or driver call for any device class, and SCPI *RST
+    // isn't reachable through it, so defaults are taken from a fresh instance:
+    // the driver is scanned again (on this device's connection, if it has one)
+    // and the new instance matching this device's DeviceId is copied over with
+    // clone_config_to. The fresh instance stays in the driver's device list.
+    // - USB, serial and Modbus devices (analyzers, meters, supplies) keep their
+    //   settings in the instance, so this restores the driver defaults.
+    // - SCPI instruments mostly read settings back from the hardware, which a
+    //   fresh, unopened instance can't do; they fail with Unsupported.
+    // - User devices have no driver and fail with Unsupported.
+    // Fails with NotFound if the rescan doesn't find the device again.
+    pub fn reset(&self) -> io::Result<()> {
+        let driver = unsafe { sr_dev_inst_driver_get(self.context) };
+        if driver.is_null() {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "User devices have no driver to reset"));
+        }
+        let driver = DriverContext {
+            driver: Driver { context: driver as *mut _ },
+        };
+        let options: Vec<ScanOption> = self.conn_id().map(ScanOption::Connection).into_iter().collect();
+        let id = self.id();
+        let fresh = driver.scan_with(&options).into_iter()
+            .find(|x| x.context != self.context && x.id() == id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Rescan didn't find the device again"))?;
+        if fresh.config_snapshot().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Driver reports no default values"));
+        }
+        if fresh.clone_config_to(self).transferred.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Device rejected every default value"));
+        }
+        Ok(())
+    }
+
+    // Copies this device's settable config to another (typically identical)
+    // device. Keys the target can't set, or rejects the value for, are skipped
+    // and listed in the result.
+    pub fn clone_config_to(&self, other: &DriverInstance) -> ConfigTransfer {
+        let mut transfer = ConfigTransfer::default();
+        for (key, value) in self.config_snapshot() {
+            if other.config_abilities(key).set && other.config_set_value(key, &value).is_ok() {
+                transfer.transferred.push(key);
+            } else {
+                transfer.skipped.push(key);
+            }
+        }
+        transfer
+    }
+
+    fn abilities(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> ConfigAbilities {
+        self.abilities_raw(group, key.to_raw())
+    }
+
+    fn abilities_raw(&self, group: Option<&DriverChannelGroup>, key: u32) -> ConfigAbilities {
+        unsafe {
+            let group = group.map(|x| x.context as *const _).unwrap_or(ptr::null());
+            let caps = sr_dev_config_capabilities_list(self.context, group, key as i32) as u32;
+            ConfigAbilities {
+                get: caps & (Enum_sr_configcap::SR_CONF_GET as u32) != 0,
+                set: caps & (Enum_sr_configcap::SR_CONF_SET as u32) != 0,
+                list: caps & (Enum_sr_configcap::SR_CONF_LIST as u32) != 0,
+            }
+        }
+    }
+
+    // Like config_set, but checks the device's abilities first so a read-only or
+    // unsupported key fails with a descriptive error instead of a driver error.
+    pub fn config_set_checked(&self, config: &ConfigOption) -> io::Result<()> {
+        let abilities = self.config_abilities(config.key());
+        if !abilities.set {
+            let msg = if abilities.get || abilities.list {
+                format!("{:?} is read-only on this device", config.key())
+            } else {
+                format!("{:?} is not supported by this device", config.key())
+            };
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, msg));
+        }
+        self.config_apply(None, config)
+    }
+
+    fn config_apply(&self, group: Option<&DriverChannelGroup>, config: &ConfigOption) -> io::Result<()> {
+        unsafe {
+            self.config_apply_variant(group, config.key(), config.to_variant())
+        }
+    }
+
+    // Takes a floating reference, as returned by to_variant().
+    unsafe fn config_apply_variant(&self, group: Option<&DriverChannelGroup>, key: ConfigKey, gvar: *mut GVariant) -> io::Result<()> {
+        let group = group.map(|x| x.context as *const _).unwrap_or(ptr::null());
+        // to_variant() returns a floating reference (tuple children are sunk by
+        // their parent). Own it explicitly so it's released exactly once here,
+        // whether or not sr_config_set sinks it on every return path.
+        let gvar = glib_sys::g_variant_ref_sink(gvar);
+        let res = sr_config_set(self.context, group, key.to_raw(), gvar);
+        glib_sys::g_variant_unref(gvar);
+        if res != 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, ConfigError::new(key, SrError(res))));
+        }
+        Ok(())
+    }
+
+    fn config_get(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<*mut GVariant> {
+        unsafe {
+            let group = group.map(|x| x.context as *const _).unwrap_or(ptr::null());
+            config_get_raw(sr_dev_inst_driver_get(self.context), self.context, group, key)
+        }
+    }
+
+    fn config_get_f64(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<f64> {
+        self.config_get(group, key).map(|gvar| unsafe {
+            let value = glib_sys::g_variant_get_double(gvar);
+            glib_sys::g_variant_unref(gvar);
+            value
+        })
+    }
+
+    fn config_get_u64(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<u64> {
+        self.config_get(group, key).map(|gvar| unsafe {
+            let value = glib_sys::g_variant_get_uint64(gvar);
+            glib_sys::g_variant_unref(gvar);
+            value
+        })
+    }
+
+    fn config_get_bool(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<bool> {
+        self.config_get(group, key).map(|gvar| unsafe {
+            let value = glib_sys::g_variant_get_boolean(gvar) != 0;
+            glib_sys::g_variant_unref(gvar);
+            value
+        })
+    }
+
+    pub fn samplerate(&self) -> Option<SampleRate> {
+        self.config_get_u64(None, ConfigKey::SampleRate).map(SampleRate)
+    }
+
+    // Enables hardware run-length encoding. Drivers expand RLE data themselves
+    // before sending it, so logic packets always carry plain samples.
+    pub fn set_rle(&self, enabled: bool) -> io::Result<()> {
+        self.config_apply(None, &ConfigOption::Rle(enabled))
+    }
+
+    pub fn rle(&self) -> Option<bool> {
+        self.config_get_bool(None, ConfigKey::Rle)
+    }
+
+    // SR_CONF_SAMPLE_INTERVAL has millisecond resolution; the interval is rounded to
+    // the nearest millisecond and anything below 1ms is rejected.
+    pub fn set_sample_interval(&self, interval: Duration) -> io::Result<()> {
+        if interval < Duration::from_millis(1) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Sample interval must be at least 1ms"));
+        }
+        let ms = interval.as_secs()
+            .checked_mul(1000)
+            .and_then(|x| x.checked_add(((interval.subsec_nanos() + 500_000) / 1_000_000) as u64));
+        match ms {
+            Some(ms) => self.config_apply(None, &ConfigOption::SampleInterval(ms)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "Sample interval is too large")),
+        }
+    }
+
+    pub fn sample_interval(&self) -> Option<Duration> {
+        self.config_get_u64(None, ConfigKey::SampleInterval).map(Duration::from_millis)
+    }
+
+    // The horizontal trigger position is the fraction of the capture window that
+    // precedes the trigger: 0.0 puts the trigger at the start, 0.25 a quarter in.
+    pub fn set_trigger_position(&self, fraction: f64) -> io::Result<()> {
+        if !(fraction >= 0.0 && fraction <= 1.0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Trigger position must be within 0.0..=1.0"));
+        }
+        self.config_apply(None, &ConfigOption::HorizTriggerPos(fraction))
+    }
+
+    pub fn trigger_position(&self) -> Option<f64> {
+        self.config_get_f64(None, ConfigKey::HorizTriggerPos)
+    }
+
+    // Tunes the downmix frequency of a demodulator, in Hz. Checked against the
+    // range the device lists, if any.
+    pub fn set_center_frequency(&self, hz: u64) -> io::Result<()> {
+        if let Some((min, max)) = self.config_list_u64_range(None, ConfigKey::CenterFrequency) {
+            if hz < min || hz > max {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("Center frequency {} Hz is outside {}..={} Hz", hz, min, max)));
+            }
+        }
+        self.config_set_checked(&ConfigOption::CenterFrequency(hz))
+    }
+
+    pub fn center_frequency(&self) -> io::Result<u64> {
+        self.config_get_u64(None, ConfigKey::CenterFrequency)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Device can't get CenterFrequency"))
+    }
+
+    // Trigger types the device accepts, as listed by SR_CONF_TRIGGER_MATCH. Empty
+    // for devices without trigger support.
+    pub fn supported_trigger_types(&self) -> io::Result<Vec<TriggerType>> {
+        let gvar = match self.config_list(None, ConfigKey::TriggerMatch) {
+            Some(gvar) => gvar,
+            None => return Ok(vec![]),
+        };
+        unsafe {
+            if variant_type(gvar) != "ai" {
+                glib_sys::g_variant_unref(gvar);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Unexpected TriggerMatch list type"));
+            }
+            let mut matches = vec![];
+            for i in 0..glib_sys::g_variant_n_children(gvar) {
+                let child = glib_sys::g_variant_get_child_value(gvar, i);
+                matches.extend(TriggerType::from_raw(glib_sys::g_variant_get_int32(child) as u32));
+                glib_sys::g_variant_unref(child);
+            }
+            glib_sys::g_variant_unref(gvar);
+            Ok(matches)
+        }
+    }
+
+    // Sets the acquisition buffer size in bytes and returns the size the device
+    // actually uses, as drivers may round it. When the device lists discrete
+    // sizes the value must be one of them.
+    pub fn set_buffer_size(&self, bytes: u64) -> io::Result<u64> {
+        if let Some(gvar) = self.config_list(None, ConfigKey::BufferSize) {
+            let allowed = unsafe {
+                let allowed = match &variant_type(gvar)[..] {
+                    "at" => (0..glib_sys::g_variant_n_children(gvar)).any(|i| {
+                        let child = glib_sys::g_variant_get_child_value(gvar, i);
+                        let size = glib_sys::g_variant_get_uint64(child);
+                        glib_sys::g_variant_unref(child);
+                        size == bytes
+                    }),
+                    _ => variant_u64_range(gvar).map_or(true, |(min, max)| bytes >= min && bytes <= max),
+                };
+                glib_sys::g_variant_unref(gvar);
+                allowed
+            };
+            if !allowed {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("Buffer size {} is not supported by this device", bytes)));
+            }
+        }
+        self.config_set_checked(&ConfigOption::BufferSize(bytes))?;
+        Ok(self.buffer_size().unwrap_or(bytes))
+    }
+
+    pub fn buffer_size(&self) -> Option<u64> {
+        self.config_get_u64(None, ConfigKey::BufferSize)
+    }
+
+    // Sets a meter's integration time in powerline cycles (NPLC). Integrating
+    // over whole cycles cancels mains hum, so 1 NPLC is 20 ms on 50 Hz mains
+    // and 16.7 ms on 60 Hz; more cycles read slower with less noise. When the
+    // device lists discrete values the value must be one of them.
+    pub fn set_nplc(&self, cycles: f64) -> io::Result<()> {
+        if let Some(gvar) = self.config_list(None, ConfigKey::AdcPowerlineCycles) {
+            let allowed = unsafe {
+                let allowed = match &variant_type(gvar)[..] {
+                    "ad" => (0..glib_sys::g_variant_n_children(gvar)).any(|i| {
+                        let child = glib_sys::g_variant_get_child_value(gvar, i);
+                        let value = glib_sys::g_variant_get_double(child);
+                        glib_sys::g_variant_unref(child);
+                        value == cycles
+                    }),
+                    _ => variant_f64_range(gvar).map_or(true, |(min, max)| cycles >= min && cycles <= max),
+                };
+                glib_sys::g_variant_unref(gvar);
+                allowed
+            };
+            if !allowed {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("{} NPLC is not supported by this device", cycles)));
+            }
+        }
+        self.config_set_checked(&ConfigOption::AdcPowerlineCycles(cycles))
+    }
+
+    pub fn nplc(&self) -> io::Result<f64> {
+        self.config_get_f64(None, ConfigKey::AdcPowerlineCycles)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Device doesn't report NPLC"))
+    }
+
+    fn config_list(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<*mut GVariant> {
+        unsafe {
+            let mut gvar: *mut GVariant = ptr::null_mut();
+            let group = group.map(|x| x.context as *const _).unwrap_or(ptr::null());
+            let res = sr_config_list(sr_dev_inst_driver_get(self.context), self.context, group, key.to_raw(), &mut gvar as *mut _);
+            if res == 0 && !gvar.is_null() {
+                Some(gvar)
+            } else {
+                None
+            }
+        }
+    }
+
+    // The current value of any gettable key, decoded by its GVariant type.
+    pub fn config_get_value(&self, key: ConfigKey) -> io::Result<ConfigValue> {
+        let gvar = self.config_get(None, key)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Device can't get {:?}", key)))?;
+        let value = unsafe {
+            let value = ConfigValue::from_variant(gvar, key.data_type());
+            glib_sys::g_variant_unref(gvar);
+            value
+        };
+        value.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported value type for {:?}", key)))
+    }
+
+    // Sets any key from a generic value. The value's type must be the one the
+    // driver expects for the key.
+    pub fn config_set_value(&self, key: ConfigKey, value: &ConfigValue) -> io::Result<()> {
+        unsafe { self.config_apply_variant(None, key, value.to_variant()) }
+    }
+
+    // GVariant type signature (e.g. "t", "(tt)") of what sr_config_get returns
+    // for `key`. Useful when a typed getter doesn't decode a driver's value.
+    pub fn config_variant_type(&self, key: ConfigKey) -> io::Result<String> {
+        match self.config_get(None, key) {
+            Some(gvar) => Ok(unsafe { owned_variant_type(gvar) }),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("Device can't get {:?}", key))),
+        }
+    }
+
+    // Same as config_variant_type, for what sr_config_list returns.
+    pub fn config_list_variant_type(&self, key: ConfigKey) -> io::Result<String> {
+        match self.config_list(None, key) {
+            Some(gvar) => Ok(unsafe { owned_variant_type(gvar) }),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("Device can't list {:?}", key))),
+        }
+    }
+
+    // Allowed values of a string-enumerated config key, e.g. the pattern modes.
+    pub fn config_string_options(&self, key: ConfigKey) -> io::Result<Vec<String>> {
+        let gvar = match self.config_list(None, key) {
+            Some(gvar) => gvar,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("Device can't list {:?}", key))),
+        };
+        unsafe {
+            let values = if variant_type(gvar) == "as" {
+                Ok(variant_strings(gvar))
+            } else {
+                Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not a string config", key)))
+            };
+            glib_sys::g_variant_unref(gvar);
+            values
+        }
+    }
+
+    pub fn timebases(&self) -> Vec<Timebase> {
+        self.config_list_rationals(ConfigKey::Timebase).into_iter().map(Timebase).collect()
+    }
+
+    pub fn vdivs(&self) -> Vec<Vdiv> {
+        self.config_list_rationals(ConfigKey::Vdiv).into_iter().map(Vdiv).collect()
+    }
+
+    fn config_list_f64_range(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<(f64, f64)> {
+        self.config_list(group, key).and_then(|gvar| unsafe {
+            let range = variant_f64_range(gvar);
+            glib_sys::g_variant_unref(gvar);
+            range
+        })
+    }
+
+    fn config_list_u64_range(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<(u64, u64)> {
+        self.config_list(group, key).and_then(|gvar| unsafe {
+            let range = variant_u64_range(gvar);
+            glib_sys::g_variant_unref(gvar);
+            range
+        })
+    }
+
+    fn config_get_string(&self, group: Option<&DriverChannelGroup>, key: ConfigKey) -> Option<String> {
+        self.config_get(group, key).map(|gvar| unsafe {
+            let value = CStr::from_ptr(glib_sys::g_variant_get_string(gvar, ptr::null_mut())).to_string_lossy().into_owned();
+            glib_sys::g_variant_unref(gvar);
+            value
+        })
+    }
+
+    // Predefined levels are validated against the device's SR_CONF_LOGIC_THRESHOLD
+    // list. A custom level selects the list's "USER"/"Custom" entry when there is
+    // one, then sets SR_CONF_LOGIC_THRESHOLD_CUSTOM within its listed range.
+    pub fn set_logic_threshold(&self, level: &LogicThresholdLevel) -> io::Result<()> {
+        let levels = self.config_string_options(ConfigKey::LogicThreshold).unwrap_or(vec![]);
+        match level {
+            &LogicThresholdLevel::Named(ref name) => {
+                if !levels.iter().any(|x| x == name) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown logic threshold {:?}", name)));
+                }
+                self.config_apply(None, &ConfigOption::LogicThreshold(name.clone()))
+            }
+            &LogicThresholdLevel::Custom(value) => {
+                if let Some((min, max)) = self.config_list_f64_range(None, ConfigKey::LogicThresholdCustom) {
+                    if value < min || value > max {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Custom logic threshold is out of range"));
+                    }
+                }
+                if let Some(custom) = levels.iter().find(|x| is_custom_threshold(x)) {
+                    self.config_apply(None, &ConfigOption::LogicThreshold(custom.clone()))?;
+                }
+                self.config_apply(None, &ConfigOption::LogicThresholdCustom(value))
+            }
+        }
+    }
+
+    pub fn logic_threshold(&self) -> Option<LogicThresholdLevel> {
+        match self.config_get_string(None, ConfigKey::LogicThreshold) {
+            Some(ref name) if !is_custom_threshold(name) => Some(LogicThresholdLevel::Named(name.clone())),
+            _ => self.config_get_f64(None, ConfigKey::LogicThresholdCustom).map(LogicThresholdLevel::Custom),
+        }
+    }
+
+    // Enables external clocking with the given source and edge, or disables it
+    // with None. The source and edge are checked against the listed options and
+    // set before the clock is switched on.
+    pub fn set_external_clock(&self, config: Option<&ExternalClockConfig>) -> io::Result<()> {
+        if !self.config_abilities(ConfigKey::ExternalClock).set {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Device doesn't support an external clock"));
+        }
+        let config = match config {
+            Some(config) => config,
+            None => return self.config_apply(None, &ConfigOption::ExternalClock(false)),
+        };
+        if let Some(ref source) = config.source {
+            let sources = self.config_string_options(ConfigKey::ExternalClockSource)?;
+            if !sources.contains(source) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown clock source {:?}", source)));
+            }
+            self.config_apply(None, &ConfigOption::ExternalClockSource(source.clone()))?;
+        }
+        if let Some(edge) = config.edge {
+            let edges = self.config_string_options(ConfigKey::ClockEdge)?;
+            let name = edges.into_iter().find(|x| ClockEdge::from_name(x) == Some(edge))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Device doesn't support {:?} clock edge", edge)))?;
+            self.config_apply(None, &ConfigOption::ClockEdge(name))?;
+        }
+        self.config_apply(None, &ConfigOption::ExternalClock(true))
+    }
+
+    // None while the device runs from its internal clock.
+    pub fn external_clock(&self) -> Option<ExternalClockConfig> {
+        if !self.config_get_bool(None, ConfigKey::ExternalClock).unwrap_or(false) {
+            return None;
+        }
+        Some(ExternalClockConfig {
+            source: self.config_get_string(None, ConfigKey::ExternalClockSource),
+            edge: self.config_get_string(None, ConfigKey::ClockEdge).and_then(|x| ClockEdge::from_name(&x)),
+        })
+    }
+
+    fn config_list_rationals(&self, key: ConfigKey) -> Vec<Ratio<u64>> {
+        match self.config_list(None, key) {
+            Some(gvar) => unsafe {
+                let values = variant_rationals(gvar);
+                glib_sys::g_variant_unref(gvar);
+                values
+            },
+            None => vec![],
+        }
+    }
+
+    // pub fn output(&self, output: &Output) {
+    //     unsafe {
+    //         let output = sr_output_new(output.context, 0x0 as *mut glib_sys::GHashTable, self.context, 0x0 as *const i8);
+    //
+    //     }
+    // }
+}
+
+#[derive(Debug, Clone)]
+pub struct DriverChannel {
+    context: *mut Struct_sr_channel,
+}
+
+impl DriverChannel {
+    pub fn index(&self) -> u32 {
+        unsafe {
+            (*self.context).index as u32
+        }
+    }
+
+    pub fn name(&self) -> String {
+        unsafe {
+            CStr::from_ptr((*self.context).name).to_string_lossy().into_owned()
+        }
+    }
+
+    pub fn is_analog(&self) -> bool {
+        unsafe {
+            (*self.context)._type == Enum_sr_channeltype::SR_CHANNEL_ANALOG as i32
+        }
+    }
+
+    pub fn is_logic(&self) -> bool {
+        unsafe {
+            (*self.context)._type == Enum_sr_channeltype::SR_CHANNEL_LOGIC as i32
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        unsafe {
+            (*self.context).enabled != 0
+        }
+    }
+
+    pub fn device(&self) -> DriverInstance {
+        unsafe {
+            DriverInstance {
+                context: (*self.context).sdi,
+            }
+        }
+    }
+
+    // Trigger types the device accepts (SR_CONF_TRIGGER_MATCH) that apply to this
+    // channel's type: levels and edges for logic channels, over/under for analog.
+    pub fn supported_triggers(&self) -> Vec<TriggerType> {
+        let analog = self.is_analog();
+        self.device().supported_trigger_types().unwrap_or_default().into_iter()
+            .filter(|&x| analog == (x == TriggerType::Over || x == TriggerType::Under))
+            .collect()
+    }
+
+    pub fn set_enabled(&self, enabled: bool) -> io::Result<()> {
+        if unsafe { sr_dev_channel_enable(self.context, enabled as glib_sys::gboolean) } != 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, format!("Could not {} channel {}",
+                                                                    if enabled { "enable" } else { "disable" },
+                                                                    self.name())));
+        }
+        Ok(())
+    }
+
+    // Like set_enabled, ignoring failures.
+    pub fn disable(&self) {
+        let _ = self.set_enabled(false);
+    }
+
+    pub fn enable(&self) {
+        let _ = self.set_enabled(true);
+    }
+}
+
+// Channels are equal when they belong to the same device and share an index.
+impl PartialEq for DriverChannel {
+    fn eq(&self, other: &DriverChannel) -> bool {
+        unsafe {
+            (*self.context).sdi == (*other.context).sdi && self.index() == other.index()
+        }
+    }
+}
+
+impl Eq for DriverChannel {}
+
+impl Drop for Sigrok {
+    fn drop(&mut self) {
+        unsafe {
+            let res = sr_exit(self.context);
+            if res == 0 {
+                // noop
+            } else {
+                panic!("Failed on sigrok context destructor")
+            }
+        }
+    }
+}
+
+// libsigrok doesn't synchronize access to a context, so a Sigrok may move between
+// threads but must never be used from two threads at once.
+unsafe impl Send for Sigrok {}
+
+// A context shared across threads. Every operation on the context (listing or
+// initializing drivers, scanning, creating sessions) has to happen while holding
+// the lock; keep the guard for as long as objects derived from it are in use.
+#[derive(Clone)]
+pub struct SharedSigrok {
+    inner: Arc<Mutex<Sigrok>>,
+}
+
+impl SharedSigrok {
+    pub fn new() -> io::Result<SharedSigrok> {
+        Ok(SharedSigrok::from(Sigrok::new()?))
+    }
+
+    // The process-wide context, created on first use and shared by every caller
+    // while any clone is alive; sr_exit runs when the last clone is dropped.
+    // Prefer this over separate Sigrok::new() calls, and don't mix the two for
+    // the same devices.
+    pub fn global() -> io::Result<SharedSigrok> {
+        let mut global = GLOBAL.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(inner) = global.as_ref().and_then(|x| x.upgrade()) {
+            return Ok(SharedSigrok { inner });
+        }
+        let ctx = SharedSigrok::new()?;
+        *global = Some(Arc::downgrade(&ctx.inner));
+        Ok(ctx)
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Sigrok> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+static GLOBAL: Mutex<Option<Weak<Mutex<Sigrok>>>> = Mutex::new(None);
+
+impl From<Sigrok> for SharedSigrok {
+    fn from(ctx: Sigrok) -> SharedSigrok {
+        SharedSigrok {
+            inner: Arc::new(Mutex::new(ctx)),
+        }
+    }
+}
+
+pub struct Session {
+    context: *mut Struct_sr_session,
+    _callbacks: Vec<Box<CallbackState>>,
+    _triggers: Option<Triggers>,
+    _stopped: Cell<bool>,
+    // Set for sessions loaded from a file.
+    file: Option<PathBuf>,
+}
+
+#[non_exhaustive]
+pub enum Datafeed<'a> {
+    Header {
+        feed_version: i32,
+        start_time: time::Timespec,
+    },
+    Logic(Logic<'a>),
+    Analog(Analog<'a>),
+    // The trigger fired. libsigrok doesn't send the trigger's position, so
+    // `sample` is the number of samples the device had sent since its header
+    // when the trigger packet arrived (logic samples, or the most of any analog
+    // channel for analog-only devices). Drivers send the packet at the trigger
+    // point, but one that buffers pre-trigger data differently may be off by a
+    // packet. None if no header was seen.
+    Trigger {
+        sample: Option<u64>,
+    },
+    // The device finished sending data for this acquisition.
+    End,
+    // The session failed (e.g. the device was unplugged) before the device
+    // sent End. Delivered in place of End so consumers can clean up.
+    Aborted,
+    FrameBegin,
+    FrameEnd,
+    // A packet type this crate doesn't model yet, e.g. SR_DF_META.
+    Unknown {
+        packet_type: u16,
+    },
+}
+
+impl<'a> fmt::Debug for Datafeed<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Datafeed::Header { feed_version, start_time } => f.debug_struct("Header")
+                .field("feed_version", &feed_version)
+                .field("start_time", &start_time)
+                .finish(),
+            &Datafeed::Logic(ref logic) => f.debug_tuple("Logic").field(logic).finish(),
+            &Datafeed::Analog(ref analog) => f.debug_tuple("Analog").field(analog).finish(),
+            &Datafeed::Trigger { sample } => f.debug_struct("Trigger").field("sample", &sample).finish(),
+            &Datafeed::End => f.write_str("End"),
+            &Datafeed::Aborted => f.write_str("Aborted"),
+            &Datafeed::FrameBegin => f.write_str("FrameBegin"),
+            &Datafeed::FrameEnd => f.write_str("FrameEnd"),
+            &Datafeed::Unknown { packet_type } => f.debug_struct("Unknown").field("packet_type", &packet_type).finish(),
+        }
+    }
+}
+
+// A registered callback, plus the per-device sample counts used to place trigger
+// packets. Boxed in the session so the pointer handed to libsigrok stays put.
+struct CallbackState {
+    callback: Box<SessionCallback>,
+    counters: HashMap<usize, SampleCounter>,
+}
+
+impl CallbackState {
+    fn new(callback: Box<SessionCallback>) -> CallbackState {
+        CallbackState {
+            callback,
+            counters: HashMap::new(),
+        }
+    }
+
+    fn as_ptr(&self) -> *mut os::raw::c_void {
+        self as *const CallbackState as *mut _
+    }
+}
+
+#[derive(Debug, Default)]
+struct SampleCounter {
+    logic: u64,
+    analog: HashMap<usize, u64>,
+}
+
+impl SampleCounter {
+    fn position(&self) -> u64 {
+        if self.logic > 0 || self.analog.is_empty() {
+            self.logic
+        } else {
+            self.analog.values().cloned().max().unwrap_or(0)
+        }
+    }
+}
+
+unsafe extern "C" fn sr_session_callback(inst: *const Struct_sr_dev_inst, packet: *const Struct_sr_datafeed_packet, data: *mut os::raw::c_void) {
+    // See session.c in sigrok-cli line 186
+    let kind = (*packet)._type;
+
+    let state = &mut *(data as *mut CallbackState);
+    let counters = &mut state.counters;
+    let cb = &mut state.callback;
+    let driver = DriverInstance {
+        context: inst as *mut _,
+    };
+
+    if kind == (Enum_sr_packettype::SR_DF_HEADER as u16) {
+        let header: *const Struct_sr_datafeed_header = (*packet).payload as usize as *const _;
+        counters.insert(inst as usize, SampleCounter::default());
+
+        cb(&driver, &Datafeed::Header {
+            feed_version: (*header).feed_version as i32,
+            start_time: time::Timespec {
+                sec: (*header).starttime.tv_sec as i64,
+                nsec: ((*header).starttime.tv_usec as i32) * 1000,
+            },
+        });
+    } else if kind == (Enum_sr_packettype::SR_DF_LOGIC as u16) {
+        let logic: *const Struct_sr_datafeed_logic = (*packet).payload as usize as *const _;
+        match Logic::from_raw(logic) {
+            Ok(logic) => {
+                if let Some(counter) = counters.get_mut(&(inst as usize)) {
+                    counter.logic += logic.len() as u64;
+                }
+                cb(&driver, &Datafeed::Logic(logic))
+            }
+            Err(err) => warn(format_args!("dropping logic packet: {}", err)),
+        }
+    } else if kind == (Enum_sr_packettype::SR_DF_ANALOG as u16) {
+        let analog: *const Struct_sr_datafeed_analog = (*packet).payload as usize as *const _;
+        match Analog::from_raw(analog) {
+            Ok(analog) => {
+                if let Some(counter) = counters.get_mut(&(inst as usize)) {
+                    for channel in &analog.channels {
+                        *counter.analog.entry(channel.context as usize).or_insert(0) += analog.num_samples as u64;
+                    }
+                }
+                cb(&driver, &Datafeed::Analog(analog))
+            }
+            Err(err) => warn(format_args!("dropping analog packet: {}", err)),
+        }
+    } else if kind == (Enum_sr_packettype::SR_DF_END as u16) {
+        counters.remove(&(inst as usize));
+        cb(&driver, &Datafeed::End);
+    } else if kind == (Enum_sr_packettype::SR_DF_TRIGGER as u16) {
+        cb(&driver, &Datafeed::Trigger {
+            sample: counters.get(&(inst as usize)).map(|x| x.position()),
+        });
+    } else if kind == (Enum_sr_packettype::SR_DF_FRAME_BEGIN as u16) {
+        cb(&driver, &Datafeed::FrameBegin);
+    } else if kind == (Enum_sr_packettype::SR_DF_FRAME_END as u16) {
+        cb(&driver, &Datafeed::FrameEnd);
+    } else {
+        // Includes SR_DF_META and the pre-0.5 SR_DF_ANALOG_OLD.
+        cb(&driver, &Datafeed::Unknown {
+            packet_type: kind,
+        });
+    }
+}
+
+// How often start_cancellable checks its flag.
+const CANCEL_POLL_MS: u32 = 50;
+
+struct CancelWatch {
+    session: *mut Struct_sr_session,
+    flag: Arc<AtomicBool>,
+    // Set once the session was stopped and the source has removed itself.
+    done: bool,
+}
+
+unsafe extern "C" fn cancel_timeout(data: glib_sys::gpointer) -> glib_sys::gboolean {
+    let watch = &mut *(data as *mut CancelWatch);
+    if watch.flag.load(Ordering::SeqCst) {
+        let _ = sr_session_stop(watch.session);
+        watch.done = true;
+        return 0;
+    }
+    1
+}
+
+// Sends Aborted for every device that started a feed (sent a header) but never
+// ended it.
+unsafe fn abort_open_feeds(data: *mut os::raw::c_void) {
+    let state = &mut *(data as *mut CallbackState);
+    for (inst, _) in state.counters.drain() {
+        let driver = DriverInstance {
+            context: inst as *mut _,
+        };
+        (state.callback)(&driver, &Datafeed::Aborted);
+    }
+}
+
+unsafe extern "C" fn poll_timeout(data: glib_sys::gpointer) -> glib_sys::gboolean {
+    *(data as *mut bool) = true;
+    0
+}
+
+pub type SessionCallback = FnMut(&DriverInstance, &Datafeed);
+
+impl Session {
+    pub fn new(ctx: &mut Sigrok) -> Option<Session> {
+        unsafe {
+            let mut session = Session {
+                context: mem::uninitialized(),
+                _callbacks: vec![],
+                _triggers: None,
+                _stopped: Cell::new(false),
+                file: None,
+            };
+            if sr_session_new(ctx.context, &mut session.context as *mut _) == 0x0 {
+                Some(session)
+            } else {
+                None
+            }
+        }
+    }
+
+    // Scans the driver and attaches every device it finds to a fresh session.
+    // Finding nothing isn't an error; per-device config is done afterwards on
+    // the returned instances.
+    pub fn from_scan(ctx: &mut Sigrok, driver: &DriverContext, options: &[ScanOption])
+            -> io::Result<(Session, Vec<DriverInstance>)> {
+        let session = Session::new(ctx)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Could not create session"))?;
+        let instances = driver.scan_with(options);
+        for instance in &instances {
+            session.add_instance(instance);
+        }
+        Ok((session, instances))
+    }
+
+    // Opens a session file (.sr). Its contents are replayed through a virtual
+    // device that libsigrok attaches to the session.
+    pub fn load<P: AsRef<Path>>(ctx: &mut Sigrok, path: P) -> io::Result<Session> {
+        let file = path.as_ref().to_path_buf();
+        let path = file.to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Session path is not valid UTF-8"))?;
+        let path = CString::new(path)?;
+        unsafe {
+            let mut session = Session {
+                context: mem::uninitialized(),
+                _callbacks: vec![],
+                _triggers: None,
+                _stopped: Cell::new(false),
+                file: Some(file),
+            };
+            if sr_session_load(ctx.context, path.as_ptr(), &mut session.context as *mut _) == 0x0 {
+                Ok(session)
+            } else {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "Could not load session file"))
+            }
+        }
+    }
+
+    // Samplerate of the first attached device that reports one. For loaded
+    // sessions this is the rate stored in the file's metadata.
+    pub fn samplerate(&self) -> Option<SampleRate> {
+        self.instances().iter().filter_map(|x| x.samplerate()).next()
+    }
+
+    // Describes the attached devices without starting acquisition. Vendor and
+    // model come from the first device; channels are collected from all of them.
+    // For loaded sessions the sample count is read from the file's stored data;
+    // otherwise it's the configured sample limit, if any.
+    pub fn metadata(&self) -> SessionMetadata {
+        let instances = self.instances();
+        let mut metadata = SessionMetadata::default();
+        if let Some(first) = instances.get(0) {
+            metadata.vendor = first.vendor();
+            metadata.model = first.model();
+        }
+        metadata.samplerate = instances.iter().filter_map(|x| x.samplerate()).next();
+        for channel in instances.iter().flat_map(|x| x.channels()) {
+            if channel.is_logic() {
+                metadata.logic_channels.push(channel.name());
+            } else if channel.is_analog() {
+                metadata.analog_channels.push(channel.name());
+            }
+        }
+        metadata.total_samples = match self.file {
+            Some(ref file) => {
+                let unit_size = instances.get(0)
+                    .and_then(|x| x.config_get_u64(None, ConfigKey::CaptureUnitSize))
+                    .unwrap_or((metadata.logic_channels.len() as u64 + 7) / 8);
+                metadata::stored_sample_count(file, unit_size).unwrap_or(None)
+            }
+            None => instances.iter()
+                .filter_map(|x| x.config_get_u64(None, ConfigKey::LimitSamples))
+                .next(),
+        };
+        metadata
+    }
+
+    pub fn callback_add(&mut self, callback: Box<SessionCallback>) {
+        unsafe {
+            let state = Box::new(CallbackState::new(callback));
+            let _ = sr_session_datafeed_callback_add(self.context, Some(sr_session_callback), state.as_ptr());
+            self._callbacks.push(state);
+        }
+    }
+
+    // Runs acquisition to completion with a borrowed callback, so the same closure
+    // can be reused across runs. Unlike start(), this blocks until the session
+    // stops; the callback isn't kept afterwards.
+    pub fn start_with_callback(&mut self, triggers: Option<Triggers>,
+                               callback: &mut FnMut(&DriverInstance, &Datafeed)) -> io::Result<StopReason> {
+        if let Some(triggers) = triggers {
+            self.set_triggers(triggers)?;
+        }
+        self.run_borrowed(callback, |session| {
+            session.begin()?;
+            session.run().into_result()
+        })
+    }
+
+    // Runs acquisition to completion with an extra callback that is only
+    // registered for this run. A failed run is returned as an error rather than
+    // as StopReason::Error.
+    fn run_once(&mut self, callback: Box<SessionCallback>) -> io::Result<StopReason> {
+        self.with_callback(callback, |session| {
+            session.begin()?;
+            session.run().into_result()
+        })
+    }
+
+    // Registers `callback` for the duration of `f` and unregisters it afterwards,
+    // whatever `f` returns.
+    fn with_callback<T, F>(&mut self, callback: Box<SessionCallback>, f: F) -> io::Result<T>
+        where F: FnOnce(&mut Session) -> io::Result<T>
+    {
+        self.callback_add(callback);
+        let res = f(self);
+        self.callback_pop();
+        res
+    }
+
+    // with_callback for a borrowed callback. libsigrok needs a 'static callback,
+    // so the borrow's lifetime is erased here, and only here. That's sound
+    // because with_callback unregisters the callback before returning, so
+    // libsigrok can't call it once the borrow ends.
+    fn run_borrowed<T, F>(&mut self, callback: &mut FnMut(&DriverInstance, &Datafeed), f: F) -> io::Result<T>
+        where F: FnOnce(&mut Session) -> io::Result<T>
+    {
+        let callback: *mut (FnMut(&DriverInstance, &Datafeed) + 'static) = unsafe { mem::transmute(callback) };
+        self.with_callback(Box::new(move |instance, data| unsafe { (*callback)(instance, data) }), f)
+    }
+
+    // Like start_with_callback, but stops acquisition once `flag` is set, e.g.
+    // from a Ctrl-C handler. The flag is checked every 50 ms from the session's
+    // main loop, so stopping can lag by that much plus the time the driver
+    // takes to wind down.
+    pub fn start_cancellable(&mut self, flag: Arc<AtomicBool>, triggers: Option<Triggers>,
+                             callback: &mut FnMut(&DriverInstance, &Datafeed)) -> io::Result<StopReason> {
+        if let Some(triggers) = triggers {
+            self.set_triggers(triggers)?;
+        }
+        self.run_borrowed(callback, |session| {
+            session.begin()?;
+            let mut watch = Box::new(CancelWatch {
+                session: session.context,
+                flag,
+                done: false,
+            });
+            let reason = unsafe {
+                let tag = glib_sys::g_timeout_add(CANCEL_POLL_MS, Some(cancel_timeout), &mut *watch as *mut CancelWatch as *mut _);
+                let reason = session.run();
+                if !watch.done {
+                    glib_sys::g_source_remove(tag);
+                }
+                reason
+            };
+            // The watch stops the session behind stop()'s back, so run() can't
+            // tell a cancelled run from a completed one.
+            match reason {
+                StopReason::Completed if watch.done => Ok(StopReason::Stopped),
+                reason => reason.into_result(),
+            }
+        })
+    }
+
+    // Runs acquisition to completion, handing each device's packets to the
+    // callback routed to it. Packets from devices without a route go to
+    // `default`, or are dropped if there is none. Blocks like
+    // start_with_callback.
+    pub fn start_routed(&mut self, triggers: Option<Triggers>,
+                        mut routes: HashMap<DriverInstance, Box<SessionCallback>>,
+                        mut default: Option<Box<SessionCallback>>) -> io::Result<StopReason> {
+        if let Some(triggers) = triggers {
+            self.set_triggers(triggers)?;
+        }
+        self.run_once(Box::new(move |instance, data| {
+            if let Some(callback) = routes.get_mut(instance) {
+                callback(instance, data);
+            } else if let Some(ref mut callback) = default {
+                callback(instance, data);
+            }
+        }))
+    }
+
+    fn begin(&self) -> io::Result<()> {
+        self._stopped.set(false);
+        unsafe {
+            if sr_session_start(self.context) != 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "Could not start session"));
+            }
+        }
+        Ok(())
+    }
+
+    // Blocks until a started session ends, and reports why it ended. libsigrok
+    // returns the same code for both, so a run counts as Stopped if stop() was
+    // called on this session since it was started.
+    //
+    // If the run fails, callbacks get Datafeed::Aborted for each device whose
+    // feed didn't end. A device that was closed or unplugged mid-run gives a
+    // NotConnected error, so callers can tell it apart and try to reconnect.
+    pub fn run(&self) -> StopReason {
+        let res = unsafe { sr_session_run(self.context) };
+        if res != 0 {
+            for state in &self._callbacks {
+                unsafe { abort_open_feeds(state.as_ptr()) };
+            }
+            let kind = if res == Enum_sr_error_code::SR_ERR_DEV_CLOSED as os::raw::c_int {
+                io::ErrorKind::NotConnected
+            } else {
+                io::ErrorKind::Other
+            };
+            StopReason::Error(io::Error::new(kind, SrError(res)))
+        } else if self._stopped.get() {
+            StopReason::Stopped
+        } else {
+            StopReason::Completed
+        }
+    }
+
+    // Unregisters the most recently added callback.
+    fn callback_pop(&mut self) {
+        // libsigrok can only drop all callbacks, so re-register the remaining ones.
+        self._callbacks.pop();
+        unsafe {
+            let _ = sr_session_datafeed_callback_remove_all(self.context);
+            for state in &self._callbacks {
+                let _ = sr_session_datafeed_callback_add(self.context, Some(sr_session_callback), state.as_ptr());
+            }
+        }
+    }
+
+    // Captures for `duration` and returns how long acquisition actually ran.
+    // If every attached device supports SR_CONF_LIMIT_MSEC they stop themselves,
+    // and each device's previous limit is restored afterwards (or cleared, if
+    // the device can't report it). Otherwise no device config is touched and
+    // the session is polled and stopped once the time is up. Blocks like
+    // start_with_callback.
+    pub fn start_for(&mut self, duration: Duration, triggers: Option<Triggers>,
+                     callback: &mut FnMut(&DriverInstance, &Datafeed)) -> io::Result<Duration> {
+        let ms = duration.as_secs()
+            .checked_mul(1000)
+            .and_then(|x| x.checked_add((duration.subsec_nanos() / 1_000_000) as u64))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Capture duration is too large"))?;
+        if let Some(triggers) = triggers {
+            self.set_triggers(triggers)?;
+        }
+
+        let instances = self.instances();
+        let native = instances.iter().all(|x| x.config_abilities(ConfigKey::LimitMsec).set);
+        let started = Instant::now();
+        if !native {
+            let deadline = started + duration;
+            return self.run_borrowed(callback, |session| session.run_until(deadline))
+                .map(|_| started.elapsed());
+        }
+
+        // A limit of 0 means no limit at all.
+        let previous: Vec<u64> = instances.iter()
+            .map(|x| x.config_get_u64(None, ConfigKey::LimitMsec).unwrap_or(0))
+            .collect();
+        let res = instances.iter()
+            .map(|x| x.config_apply(None, &ConfigOption::LimitMsec(ms.max(1))))
+            .collect::<io::Result<Vec<()>>>()
+            .and_then(|_| self.start_with_callback(None, callback));
+        let elapsed = started.elapsed();
+        for (instance, &limit) in instances.iter().zip(&previous) {
+            let _ = instance.config_apply(None, &ConfigOption::LimitMsec(limit));
+        }
+        res.map(|_| elapsed)
+    }
+
+    fn run_until(&mut self, deadline: Instant) -> io::Result<()> {
+        self.begin()?;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            if !self.poll(deadline - now)? {
+                return Ok(());
+            }
+        }
+        self.stop()?;
+        // Let the session deliver its remaining packets and wind down.
+        while self.poll(Duration::from_millis(100))? {}
+        Ok(())
+    }
+
+    pub fn set_triggers(&mut self, triggers: Triggers) -> io::Result<()> {
+        unsafe {
+            if sr_session_trigger_set(self.context, triggers.context) != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Could not set session triggers"));
+            }
+        }
+        self._triggers = Some(triggers);
+        Ok(())
+    }
+
+    // Removes any triggers so the next run starts immediately. Clearing when no
+    // triggers are set is fine.
+    pub fn clear_triggers(&mut self) -> io::Result<()> {
+        unsafe {
+            if sr_session_trigger_set(self.context, ptr::null_mut()) != 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "Could not clear session triggers"));
+            }
+        }
+        // Only free the old triggers once the session no longer refers to them.
+        self._triggers = None;
+        Ok(())
+    }
+
+    pub fn add_instance(&self, instance: &DriverInstance) {
+        unsafe {
+            let _ = sr_dev_open(instance.context);
+            let _ = sr_session_dev_add(self.context, instance.context);
+        }
+    }
+
+    // Detaches and closes a device previously attached with add_instance. Returns
+    // a NotFound error if the device isn't attached to this session.
+    pub fn remove_instance(&self, instance: &DriverInstance) -> io::Result<()> {
+        if !self.has_instance(instance) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Device is not attached to session"));
+        }
+        unsafe {
+            if sr_session_dev_remove(self.context, instance.context) != 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "Could not remove device from session"));
+            }
+            let _ = sr_dev_close(instance.context);
+        }
+        Ok(())
+    }
+
+    fn has_instance(&self, instance: &DriverInstance) -> bool {
+        self.instances().iter().any(|x| x.context == instance.context)
+    }
+
+    // Re-reads the session's device list, e.g. after a replay attached a
+    // virtual device or a device was added or removed elsewhere.
+    pub fn refresh_devices(&self) -> Vec<DriverInstance> {
+        self.instances()
+    }
+
+    fn instances(&self) -> Vec<DriverInstance> {
+        let mut instances = vec![];
+        unsafe {
+            let mut devlist: *mut GSList = ptr::null_mut();
+            if sr_session_dev_list(self.context, &mut devlist as *mut _) != 0 {
+                return instances;
+            }
+            let mut gslist = devlist;
+            while !gslist.is_null() {
+                instances.push(DriverInstance {
+                    context: (*gslist).data as *mut Struct_sr_dev_inst,
+                });
+                gslist = (*gslist).next;
+            }
+            glib_sys::g_slist_free(devlist);
+        }
+        instances
+    }
+
+    // Registers a consumer to receive this session's packets, like callback_add.
+    pub fn consumer_add<C: DatafeedConsumer + 'static>(&mut self, mut consumer: C) {
+        self.callback_add(Box::new(move |device, data| consumer.on_datafeed(device, data)));
+    }
+
+    // Registers a consumer and starts acquisition.
+    pub fn start_consumer<C: DatafeedConsumer + 'static>(&mut self, consumer: C) {
+        self.consumer_add(consumer);
+        self.start();
+    }
+
+    pub fn start(&self) {
+        let _ = self.begin();
+    }
+
+    pub fn stop(&self) -> io::Result<()> {
+        self._stopped.set(true);
+        unsafe {
+            if sr_session_stop(self.context) != 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "Could not stop session"));
+            }
+        }
+        Ok(())
+    }
+
+    // Sets the triggers (if any), registers the callback and starts acquisition.
+    // Acquisition is stopped when the returned guard is dropped.
+    pub fn arm(&mut self, triggers: Option<Triggers>, callback: Box<SessionCallback>) -> io::Result<RunningSession<'_>> {
+        if let Some(triggers) = triggers {
+            self.set_triggers(triggers)?;
+        }
+        self.callback_add(callback);
+        self.begin()?;
+        Ok(RunningSession {
+            session: self,
+        })
+    }
+
+    // Runs one iteration of the default main context, dispatching any ready session
+    // sources and waiting at most `timeout` for one. Returns whether the session is
+    // still running. start() must have been called first to arm acquisition.
+    pub fn poll(&self, timeout: Duration) -> io::Result<bool> {
+        unsafe {
+            if timeout == Duration::from_secs(0) {
+                glib_sys::g_main_context_iteration(ptr::null_mut(), 0);
+            } else {
+                let mut fired = false;
+                let ms = timeout.as_secs().saturating_mul(1000) + (timeout.subsec_nanos() / 1_000_000) as u64;
+                let tag = glib_sys::g_timeout_add(ms.min(u32::max_value() as u64) as u32, Some(poll_timeout), &mut fired as *mut bool as *mut _);
+                glib_sys::g_main_context_iteration(ptr::null_mut(), 1);
+                if !fired {
+                    glib_sys::g_source_remove(tag);
+                }
+            }
+            match sr_session_is_running(self.context) {
+                res if res < 0 => Err(io::Error::new(io::ErrorKind::Other, "Could not query session state")),
+                res => Ok(res != 0),
+            }
+        }
+    }
+
+    // Builds the triggers against the devices attached at this point, registers
+    // the callback and starts acquisition.
+    pub fn start_with<F>(&mut self, build: F, callback: Box<SessionCallback>) -> io::Result<()>
+        where F: FnOnce(&mut TriggerBuilder) -> io::Result<()>
+    {
+        let mut builder = TriggerBuilder::new(self.instances());
+        build(&mut builder)?;
+        if let Some(triggers) = builder.build()? {
+            self.set_triggers(triggers)?;
+        }
+        self.callback_add(callback);
+        self.start();
+        Ok(())
+    }
+}
+
+// Why a session run ended.
+#[derive(Debug)]
+pub enum StopReason {
+    // The devices finished acquisition on their own, e.g. at a sample limit.
+    Completed,
+    // stop() was called while the session was running.
+    Stopped,
+    Error(io::Error),
+}
+
+impl StopReason {
+    // For the blocking run helpers, which report a failed run as an error.
+    fn into_result(self) -> io::Result<StopReason> {
+        match self {
+            StopReason::Error(err) => Err(err),
+            reason => Ok(reason),
+        }
+    }
+}
+
+pub struct RunningSession<'a> {
+    session: &'a mut Session,
+}
+
+impl<'a> Deref for RunningSession<'a> {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        self.session
+    }
+}
+
+impl<'a> Drop for RunningSession<'a> {
+    fn drop(&mut self) {
+        let _ = self.session.stop();
+    }
+}
+
+pub fn main_loop() {
+    unsafe {
+        let main_loop = g_main_loop_new(0x0 as *mut _, 0);
+        g_main_loop_run(main_loop);
+    }
+}
+
+#[cfg(test)]
+fn it_works_datafeed(_: &DriverInstance, data: &Datafeed) {
+    match data {
+        &Datafeed::Logic(ref logic) => {
+            for i in 0..64 {
+                println!("{}", format!("{:08b}", logic.data[i]).replace("1", ".").replace("0", "X"));
+            }
+            println!("");
+            ::std::process::exit(0);
+        }
+        _ => { }
+    }
+}
+
+#[test]
+fn it_works() {
+    // Print out available drivers.
+    let mut ctx = Sigrok::new().unwrap();
+    for driver in ctx.drivers() {
         println!("- {:?}: {} v{}", driver.name(), driver.long_name(), driver.api_version());
     }
 
@@ -447,3 +2561,577 @@ fn it_works() {
         main_loop();
     }
 }
+
+#[test]
+fn it_decodes_timebases() {
+    unsafe {
+        let mut tuples = [
+            rational_variant(&Ratio::new_raw(1, 1000)),
+            rational_variant(&Ratio::new_raw(500, 1)),
+        ];
+        let list = glib_sys::g_variant_ref_sink(glib_sys::g_variant_new_array(ptr::null(), tuples.as_mut_ptr(), tuples.len()));
+        let values = variant_rationals(list);
+        glib_sys::g_variant_unref(list);
+
+        assert_eq!(values.len(), 2);
+        assert_eq!((*values[0].numer(), *values[0].denom()), (1, 1000));
+        assert_eq!((*values[1].numer(), *values[1].denom()), (500, 1));
+    }
+}
+
+#[test]
+fn it_shares_context_across_threads() {
+    let shared = SharedSigrok::new().unwrap();
+    let count = shared.lock().drivers().len();
+
+    let threads: Vec<_> = (0..4).map(|_| {
+        let shared = shared.clone();
+        ::std::thread::spawn(move || shared.lock().drivers().len())
+    }).collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), count);
+    }
+}
+
+#[test]
+fn it_reuses_global_context() {
+    let first = Sigrok::global().unwrap();
+    let second = Sigrok::global().unwrap();
+    assert!(Arc::ptr_eq(&first.inner, &second.inner));
+}
+
+#[test]
+fn it_sets_config_repeatedly() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        let set = |n: u64| for i in 0..n {
+            device.config_set_checked(&ConfigOption::SampleRate(1_000 + i % 1_000)).unwrap();
+        };
+        // Warm up first so allocator pools don't count as growth. A leaked
+        // variant per call would be well over 10 MB at 200,000 calls.
+        set(10_000);
+        let before = resident_bytes();
+        set(200_000);
+        if let (Some(before), Some(after)) = (before, resident_bytes()) {
+            assert!(after < before + (4 << 20), "resident memory grew from {} to {} bytes", before, after);
+        }
+        device.config_set_checked(&ConfigOption::SampleRate(1_000_000)).unwrap();
+        assert_eq!(device.samplerate(), Some(SampleRate(1_000_000)));
+    }
+}
+
+// Resident set size from /proc, assuming 4 KiB pages. None off Linux.
+#[cfg(test)]
+fn resident_bytes() -> Option<u64> {
+    let statm = ::std::fs::read_to_string("/proc/self/statm").ok()?;
+    statm.split_whitespace().nth(1)?.parse::<u64>().ok().map(|x| x * 4096)
+}
+
+#[test]
+fn it_looks_up_channels() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        assert_eq!(device.total_channels(), device.channels().len());
+        let first = device.channels()[0].clone();
+        assert_eq!(device.channel_by_index(first.index()), Some(first.clone()));
+        assert_eq!(device.channel_by_name(&first.name()), Some(first));
+        assert_eq!(device.channel_by_index(u32::max_value()), None);
+    }
+}
+
+#[test]
+fn it_sets_probe_factor_where_supported() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        for group in device.channel_groups() {
+            if device.abilities(Some(&group), ConfigKey::ProbeFactor).set {
+                group.set_probe_factor(10).unwrap();
+                assert_eq!(group.probe_factor(), Some(10));
+            } else {
+                assert!(group.set_probe_factor(10).is_err());
+            }
+        }
+    }
+}
+
+#[test]
+fn it_queries_driver_scope_config() {
+    let ctx = Sigrok::new().unwrap();
+    for driver in ctx.drivers() {
+        if let Some(driver) = ctx.init_driver(&driver) {
+            // Must not crash for drivers that require a device instance.
+            if let Some(samplerate) = driver.samplerate() {
+                assert!(samplerate.0 > 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn it_maps_scan_option_keys() {
+    assert_eq!(u32::from(&ScanOption::Connection("/dev/ttyUSB0".to_string())), Enum_sr_configkey::SR_CONF_CONN as u32);
+    assert_eq!(u32::from(&ScanOption::ModbusAddr(1)), Enum_sr_configkey::SR_CONF_MODBUSADDR as u32);
+    let raw = ScanOption::Raw {
+        key: 12345,
+        value: RawScanValue::Bool(true),
+    };
+    assert_eq!(u32::from(&raw), 12345);
+}
+
+#[test]
+fn it_rejects_non_generators() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        assert!(SignalGenerator::new(device).is_err());
+    }
+}
+
+#[test]
+fn it_rejects_non_power_supplies() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        assert!(PowerSupply::new(device).is_err());
+    }
+}
+
+#[test]
+fn it_reports_config_variant_types() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        assert_eq!(device.config_variant_type(ConfigKey::SampleRate).unwrap(), "t");
+        assert!(device.config_variant_type(ConfigKey::OutputFrequency).is_err());
+    }
+}
+
+#[test]
+fn it_replays_csv_input() {
+    use std::rc::Rc;
+
+    let mut ctx = Sigrok::new().unwrap();
+    let mut ses = Session::new(&mut ctx).unwrap();
+    let input = Input::new("csv", &[("samplerate", "1000")]).unwrap();
+    input.send(b"0,1\n").unwrap();
+    let device = input.device().unwrap();
+    ses.add_instance(&device);
+
+    let samples = Rc::new(RefCell::new(vec![]));
+    let sink = samples.clone();
+    ses.callback_add(Box::new(move |_, data| {
+        if let &Datafeed::Logic(ref logic) = data {
+            sink.borrow_mut().extend_from_slice(logic.data);
+        }
+    }));
+    input.send(b"1,0\n1,1\n").unwrap();
+    input.end().unwrap();
+
+    // First column is channel 0, i.e. bit 0.
+    assert_eq!(*samples.borrow(), vec![0b10, 0b01, 0b11]);
+}
+
+#[test]
+fn it_reports_user_inst_type() {
+    let _ctx = Sigrok::new().unwrap();
+    let device = DriverInstance::new_user("Acme", "Probe", "1.0").unwrap();
+    assert_eq!(device.inst_type(), Some(InstType::User));
+}
+
+#[test]
+fn it_captures_logic_samples() {
+    let options = testing::DemoOptions {
+        pattern: Some("incremental".to_owned()),
+        ..Default::default()
+    };
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&options)) {
+        let capture = demo.device.capture_logic(&mut demo.session, 1000).unwrap();
+        assert!(capture.unit_size > 0);
+        assert_eq!(capture.len(), 1000);
+    }
+}
+
+#[test]
+fn it_reuses_borrowed_callback() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        demo.device.config_set_checked(&ConfigOption::LimitSamples(100)).unwrap();
+
+        let mut headers = 0;
+        {
+            let mut count = |_: &DriverInstance, data: &Datafeed| {
+                if let &Datafeed::Header { .. } = data {
+                    headers += 1;
+                }
+            };
+            demo.session.start_with_callback(None, &mut count).unwrap();
+            let reason = demo.session.start_with_callback(None, &mut count).unwrap();
+            assert!(match reason { StopReason::Completed => true, _ => false });
+        }
+        assert_eq!(headers, 2);
+
+        let running = demo.session.arm(None, Box::new(|_, _| {})).unwrap();
+        running.stop().unwrap();
+        assert!(match running.run() { StopReason::Stopped => true, _ => false });
+    }
+}
+
+#[test]
+fn it_lists_supported_triggers_per_channel() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        let all = device.supported_trigger_types().unwrap();
+        for channel in device.channels() {
+            let triggers = channel.supported_triggers();
+            assert!(triggers.iter().all(|x| all.contains(x)));
+            if channel.is_logic() {
+                assert!(triggers.contains(&TriggerType::Rising));
+            }
+            assert!(!channel.is_logic() || !triggers.contains(&TriggerType::Over));
+            assert!(!channel.is_analog() || !triggers.contains(&TriggerType::Rising));
+        }
+    }
+}
+
+#[test]
+fn it_round_trips_generic_config_values() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        device.config_set_value(ConfigKey::SampleRate, &ConfigValue::U64(200_000)).unwrap();
+        assert_eq!(device.config_get_value(ConfigKey::SampleRate).unwrap(), ConfigValue::U64(200_000));
+    }
+}
+
+#[test]
+fn it_toggles_channel_groups() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        for group in device.channel_groups() {
+            group.disable_all().unwrap();
+            assert!(group.channels().iter().all(|x| !x.is_enabled()));
+            group.enable_all().unwrap();
+            assert!(group.channels().iter().all(|x| x.is_enabled()));
+        }
+        let channel = &device.channels()[0];
+        channel.set_enabled(false).unwrap();
+        assert!(!channel.is_enabled());
+        channel.set_enabled(true).unwrap();
+        assert!(channel.is_enabled());
+    }
+}
+
+#[test]
+fn it_sorts_config_options() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        let keys: Vec<u32> = device.config_options().iter().map(|x| x.0.to_raw()).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        assert!(device.config_options().iter().any(|x| x.0 == ConfigKey::SampleRate && x.1.set));
+    }
+}
+
+#[test]
+fn it_builds_session_from_scan() {
+    let mut ctx = Sigrok::new().unwrap();
+    let demo = ctx.drivers().into_iter().find(|x| x.name() == "demo");
+    if let Some(driver) = demo {
+        let demo = ctx.init_driver(&driver).unwrap();
+        let (session, devices) = Session::from_scan(&mut ctx, &demo, &[]).unwrap();
+        assert_eq!(session.refresh_devices().len(), devices.len());
+        if let Some(device) = devices.get(0) {
+            session.remove_instance(device).unwrap();
+            assert_eq!(session.refresh_devices().len(), devices.len() - 1);
+        }
+    }
+}
+
+#[test]
+fn it_rejects_center_frequency_without_demodulator() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        assert!(!demo.device.functions().contains(&Function::Demodulator));
+        assert!(demo.device.set_center_frequency(100_000_000).is_err());
+        assert!(demo.device.center_frequency().is_err());
+    }
+}
+
+#[test]
+fn it_resolves_group_triggers() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        for group in demo.device.channel_groups() {
+            let logic = group.channels().iter().any(|x| x.is_logic());
+            match Trigger::for_group(&group, TriggerType::Rising, 0.0) {
+                Ok(trigger) => assert!(group.channels().contains(trigger.channel())),
+                Err(_) => assert!(!logic),
+            }
+        }
+    }
+}
+
+#[test]
+fn it_builds_with_log_level() {
+    let ctx = Sigrok::builder().log_level(LogLevel::Warn).build().unwrap();
+    assert_eq!(ctx.log_level(), Some(LogLevel::Warn));
+}
+
+#[test]
+fn it_counts_samples_for_trigger_position() {
+    let mut counter = SampleCounter::default();
+    assert_eq!(counter.position(), 0);
+    counter.analog.insert(1, 40);
+    counter.analog.insert(2, 50);
+    assert_eq!(counter.position(), 50);
+    counter.logic = 1000;
+    assert_eq!(counter.position(), 1000);
+}
+
+#[test]
+fn it_sets_buffer_size_where_supported() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        if demo.device.config_abilities(ConfigKey::BufferSize).set {
+            assert!(demo.device.set_buffer_size(4096).is_ok());
+        } else {
+            assert!(demo.device.set_buffer_size(4096).is_err());
+        }
+    }
+}
+
+#[test]
+fn it_captures_for_a_duration() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        demo.device.config_set(&ConfigOption::LimitMsec(0));
+        let mut packets = 0;
+        let elapsed = demo.session.start_for(Duration::from_millis(100), None, &mut |_, data| {
+            if let &Datafeed::Logic(_) = data {
+                packets += 1;
+            }
+        }).unwrap();
+        assert!(packets > 0);
+        assert!(elapsed < Duration::from_secs(10));
+        // The device's own limit is put back.
+        if demo.device.config_abilities(ConfigKey::LimitMsec).get {
+            assert_eq!(demo.device.config_get_u64(None, ConfigKey::LimitMsec), Some(0));
+        }
+    }
+}
+
+#[test]
+fn it_names_clock_edges() {
+    assert_eq!(ClockEdge::from_name("rising"), Some(ClockEdge::Rising));
+    assert_eq!(ClockEdge::from_name("F"), Some(ClockEdge::Falling));
+    assert_eq!(ClockEdge::from_name("both"), None);
+    assert_eq!(ClockEdge::from_name(""), None);
+}
+
+#[test]
+fn it_limits_captured_frames() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        if demo.device.config_abilities(ConfigKey::LimitFrames).set {
+            let frames = demo.device.capture_frames(&mut demo.session, 2).unwrap();
+            assert!(frames.len() <= 2);
+        } else {
+            assert!(demo.device.set_frame_limit(2).is_err());
+        }
+    }
+}
+
+#[test]
+fn it_rejects_unreadable_session_files() {
+    let mut ctx = Sigrok::new().unwrap();
+    assert!(ctx.open_session_file("/nonexistent/capture.sr").is_err());
+}
+
+#[test]
+fn it_reads_metadata_from_session_files() {
+    let mut ctx = Sigrok::new().unwrap();
+    let session = Session::load(&mut ctx, concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/logic.sr")).unwrap();
+    let metadata = session.metadata();
+    assert_eq!(metadata.logic_channels.len(), 8);
+    assert_eq!(metadata.total_samples, Some(1500));
+    assert_eq!(metadata.samplerate, Some(SampleRate(1_000_000)));
+    assert_eq!(metadata.to_string(), "8 logic channels, 1500 samples @ 1 MHz");
+}
+
+#[test]
+fn it_cancels_through_a_flag() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let flag = Arc::new(AtomicBool::new(false));
+        let cancel = flag.clone();
+        let reason = demo.session.start_cancellable(flag, None, &mut |_, data| {
+            if let &Datafeed::Logic(_) = data {
+                cancel.store(true, Ordering::SeqCst);
+            }
+        }).unwrap();
+        assert!(match reason { StopReason::Stopped => true, _ => false });
+    }
+}
+
+#[test]
+fn it_aborts_unfinished_feeds() {
+    let aborted = Rc::new(RefCell::new(vec![]));
+    let sink = aborted.clone();
+    let mut state = CallbackState::new(Box::new(move |device: &DriverInstance, data: &Datafeed| {
+        if let &Datafeed::Aborted = data {
+            sink.borrow_mut().push(device.context as usize);
+        }
+    }));
+    state.counters.insert(0x10, SampleCounter::default());
+    unsafe {
+        abort_open_feeds(state.as_ptr());
+        abort_open_feeds(state.as_ptr());
+    }
+    assert_eq!(*aborted.borrow(), vec![0x10]);
+}
+
+#[test]
+fn it_clones_config_between_devices() {
+    let source = testing::demo_device(&testing::DemoOptions { samplerate: Some(200_000), ..Default::default() });
+    let target = testing::demo_device(&Default::default());
+    if let (Ok(source), Ok(target)) = (source, target) {
+        let transfer = source.device.clone_config_to(&target.device);
+        assert!(transfer.transferred.contains(&ConfigKey::SampleRate));
+        assert_eq!(target.device.samplerate(), Some(SampleRate(200_000)));
+    }
+}
+
+#[test]
+fn it_routes_packets_per_device() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        demo.device.config_set_checked(&ConfigOption::LimitSamples(100)).unwrap();
+        let headers = Rc::new(RefCell::new(0));
+        let sink = headers.clone();
+        let mut routes: HashMap<DriverInstance, Box<SessionCallback>> = HashMap::new();
+        routes.insert(demo.device.clone(), Box::new(move |_, data| {
+            if let &Datafeed::Header { .. } = data {
+                *sink.borrow_mut() += 1;
+            }
+        }));
+        demo.session.start_routed(None, routes, Some(Box::new(|_, _| panic!("unrouted packet")))).unwrap();
+        assert_eq!(*headers.borrow(), 1);
+    }
+}
+
+#[test]
+fn it_collects_a_single_trigger_stage() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let logic: Vec<DriverChannel> = demo.device.channels().into_iter().filter(|x| x.is_logic()).collect();
+        let triggers: Triggers = logic.iter().map(|x| Trigger::new(x, TriggerType::Rising)).collect();
+        assert_eq!(triggers.stages().len(), 1);
+        assert_eq!(triggers.stages()[0].triggers.len(), logic.len());
+        let triggers: io::Result<Triggers> = logic.iter().map(|x| Trigger::new(x, TriggerType::Rising)).collect();
+        assert_eq!(triggers.unwrap().stages().len(), 1);
+        // libsigrok only takes edge and level matches on logic channels.
+        assert!(Triggers::try_from_iter(logic.iter().map(|x| Trigger::new(x, TriggerType::Over))).is_err());
+    }
+}
+
+#[test]
+fn it_lays_out_enabled_analog_channels() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let analog: Vec<DriverChannel> = demo.device.channels().into_iter().filter(|x| x.is_analog()).collect();
+        if analog.len() < 2 {
+            return;
+        }
+        analog[0].set_enabled(false).unwrap();
+        let layout = demo.device.analog_channel_layout();
+        assert_eq!(layout, &analog[1..]);
+
+        let mut seen = vec![];
+        demo.device.config_set(&ConfigOption::LimitSamples(8));
+        demo.session.start_with_callback(None, &mut |_, data| {
+            if let Datafeed::Analog(ref analog) = *data {
+                seen.extend(analog.channels.iter().cloned());
+            }
+        }).unwrap();
+        assert!(seen.iter().all(|x| layout.contains(x)));
+    }
+}
+
+#[test]
+fn it_waits_after_applying_config() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let start = Instant::now();
+        let settle = Duration::from_millis(20);
+        demo.device.config_apply_and_settle(None, &ConfigOption::LimitSamples(100), settle).unwrap();
+        assert!(start.elapsed() >= settle);
+    }
+}
+
+#[test]
+fn it_captures_mixed_signals() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::mixed_signal_demo(&Default::default())) {
+        let capture = demo.device.capture_mixed(&mut demo.session, 1000).unwrap();
+        assert_eq!(capture.logic().len(), 1000);
+        assert!(!capture.analog().is_empty());
+        assert_eq!(capture.len(), 1000);
+        assert_eq!(capture.time_of_sample(1000), Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn it_reports_abilities_for_raw_keys() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        let key = ConfigKey::SampleRate;
+        assert_eq!(demo.device.config_abilities_raw(key.to_raw()), demo.device.config_abilities(key));
+        assert_eq!(demo.device.config_abilities_raw(0), ConfigAbilities::default());
+    }
+}
+
+#[test]
+fn it_catalogs_drivers() {
+    let ctx = Sigrok::new().unwrap();
+    let catalog = ctx.driver_catalog();
+    assert_eq!(catalog.len(), ctx.drivers().len());
+    if catalog.get("demo").is_some() {
+        let functions = catalog.functions("demo").unwrap();
+        assert_eq!(catalog.functions("demo").unwrap(), functions);
+        let cached = ctx.driver_capabilities().into_iter().find(|x| x.0 == "demo").map(|x| x.1);
+        assert_eq!(cached, Some(functions));
+    }
+    assert!(catalog.functions("no-such-driver").is_err());
+}
+
+#[test]
+fn it_rejects_nplc_without_support() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        if !demo.device.config_abilities(ConfigKey::AdcPowerlineCycles).set {
+            assert!(demo.device.set_nplc(10.0).is_err());
+            assert!(demo.device.nplc().is_err());
+        }
+    }
+}
+
+#[test]
+fn it_resets_to_driver_defaults() {
+    if let Some(demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        assert_eq!(demo.device.samplerate(), Some(SampleRate(1_000_000)));
+        demo.device.reset().unwrap();
+        assert!(demo.device.samplerate().is_some());
+        assert_ne!(demo.device.samplerate(), Some(SampleRate(1_000_000)));
+    }
+}
+
+#[test]
+fn it_filters_warnings_by_log_level() {
+    let seen = RefCell::new(vec![]);
+    let handler = |msg: &str| seen.borrow_mut().push(msg.to_owned());
+    dispatch_warning(Some(LogLevel::Warn), Some(&handler), format_args!("shown {}", 1));
+    dispatch_warning(Some(LogLevel::Spew), Some(&handler), format_args!("verbose"));
+    dispatch_warning(Some(LogLevel::Error), Some(&handler), format_args!("quiet"));
+    dispatch_warning(None, Some(&handler), format_args!("unknown level"));
+    dispatch_warning(Some(LogLevel::Warn), None, format_args!("no handler"));
+    assert_eq!(*seen.borrow(), vec!["shown 1", "verbose"]);
+}
+
+#[test]
+fn it_decodes_analog_values_like_libsigrok() {
+    if let Some(mut demo) = testing::skip_if_missing(testing::demo_device(&Default::default())) {
+        demo.device.config_set(&ConfigOption::LimitSamples(100));
+        let mut checked = 0;
+        demo.session.start_with_callback(None, &mut |_, data| {
+            if let Datafeed::Analog(ref analog) = *data {
+                let values: Vec<f32> = analog.iter_values().unwrap().collect();
+                assert_eq!(values, analog.to_float().unwrap());
+                checked += 1;
+            }
+        }).unwrap();
+        assert!(checked > 0);
+    }
+}
@@ -0,0 +1,215 @@
+use sigrok_sys::Struct_sr_datafeed_logic;
+use std::fmt;
+use std::io;
+use std::slice;
+use std::time::Duration;
+
+// A logic packet: `data` holds consecutive samples of `unit_size` bytes each,
+// with channel N at bit N of a sample.
+#[derive(Clone, Copy)]
+pub struct Logic<'a> {
+    pub unit_size: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Logic<'a> {
+    // Checks the C-reported length and data pointer before building the slice, so
+    // a malformed packet (e.g. from a corrupt session file) is rejected.
+    pub(crate) unsafe fn from_raw(logic: *const Struct_sr_datafeed_logic) -> io::Result<Logic<'a>> {
+        if logic.is_null() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Logic packet has no payload"));
+        }
+        let length = match logic_data_len((*logic).length as u64) {
+            Some(length) => length,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Logic packet length is out of range")),
+        };
+        let data = if length == 0 {
+            &[][..]
+        } else if (*logic).data.is_null() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Logic packet has no data"));
+        } else {
+            slice::from_raw_parts::<u8>((*logic).data as *const u8, length)
+        };
+        Ok(Logic {
+            unit_size: (*logic).unitsize as u32,
+            data,
+        })
+    }
+
+    // Number of whole samples in the packet; 0 if the unit size is 0.
+    pub fn len(&self) -> usize {
+        match self.unit_size as usize {
+            0 => 0,
+            size => self.data.len() / size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The `unit_size` bytes of sample `n`, or None past the end of the packet.
+    pub fn sample(&self, n: usize) -> Option<&'a [u8]> {
+        if n >= self.len() {
+            return None;
+        }
+        let size = self.unit_size as usize;
+        Some(&self.data[n * size..(n + 1) * size])
+    }
+
+    // Level of one channel in every sample. Empty if the channel doesn't fit in
+    // the unit size.
+    pub fn levels(&self, channel: u32) -> impl Iterator<Item = bool> + 'a {
+        let size = self.unit_size as usize;
+        let (byte, mask) = ((channel / 8) as usize, 1u8 << (channel % 8));
+        let len = if byte < size { self.len() } else { 0 };
+        self.data[..len * size]
+            .chunks(size.max(1))
+            .map(move |sample| sample[byte] & mask != 0)
+    }
+
+    // Sample indices where the channel changes level, with the new level. The
+    // first sample always counts as a transition, since the level before the
+    // packet is unknown.
+    pub fn transitions(&self, channel: u32) -> impl Iterator<Item = (usize, bool)> + 'a {
+        self.levels(channel)
+            .enumerate()
+            .scan(None, |last, (i, level)| {
+                let changed = *last != Some(level);
+                *last = Some(level);
+                Some((i, level, changed))
+            })
+            .filter(|&(_, _, changed)| changed)
+            .map(|(i, level, _)| (i, level))
+    }
+
+    // Pairs each sample with its time since the start of the capture, counting
+    // from `start_sample` so timing can continue across packets. With a
+    // samplerate of 0 the time is unknown and every sample is at zero.
+    pub fn timed_samples(&self, samplerate: u64, start_sample: u64) -> impl Iterator<Item = (Duration, &'a [u8])> {
+        let size = self.unit_size as usize;
+        let len = self.len();
+        self.data[..len * size]
+            .chunks(size.max(1))
+            .enumerate()
+            .map(move |(i, sample)| (sample_time(start_sample + i as u64, samplerate), sample))
+    }
+}
+
+// Summarizes instead of dumping the sample buffer.
+impl<'a> fmt::Debug for Logic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Logic")
+            .field("unit_size", &self.unit_size)
+            .field("samples", &self.len())
+            .finish()
+    }
+}
+
+// A slice can't span more than isize::MAX bytes.
+fn logic_data_len(length: u64) -> Option<usize> {
+    if length > isize::max_value() as u64 {
+        None
+    } else {
+        Some(length as usize)
+    }
+}
+
+// Logic samples collected from any number of packets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogicCapture {
+    pub unit_size: u32,
+    pub data: Vec<u8>,
+}
+
+impl LogicCapture {
+    // Appends a packet. The unit size is taken from the first packet.
+    pub fn push(&mut self, logic: &Logic) {
+        if self.data.is_empty() {
+            self.unit_size = logic.unit_size;
+        }
+        self.data.extend_from_slice(logic.data);
+    }
+
+    pub fn as_logic(&self) -> Logic<'_> {
+        Logic {
+            unit_size: self.unit_size,
+            data: &self.data,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_logic().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn sample(&self, n: usize) -> Option<&[u8]> {
+        self.as_logic().sample(n)
+    }
+}
+
+pub(crate) fn sample_time(n: u64, samplerate: u64) -> Duration {
+    if samplerate == 0 {
+        return Duration::from_secs(0);
+    }
+    let nanos = n as u128 * 1_000_000_000 / samplerate as u128;
+    Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+#[test]
+fn it_indexes_samples() {
+    let data = [0x01, 0x80, 0x02, 0x40, 0xff];
+    let logic = Logic { unit_size: 2, data: &data };
+    assert_eq!(logic.len(), 2);
+    assert_eq!(logic.sample(0), Some(&data[0..2]));
+    assert_eq!(logic.sample(1), Some(&data[2..4]));
+    assert_eq!(logic.sample(2), None);
+
+    let empty = Logic { unit_size: 0, data: &data };
+    assert_eq!(empty.len(), 0);
+    assert_eq!(empty.sample(0), None);
+}
+
+#[test]
+fn it_summarizes_in_debug_output() {
+    let data = [0u8; 64];
+    let logic = Logic { unit_size: 2, data: &data };
+    assert_eq!(format!("{:?}", logic), "Logic { unit_size: 2, samples: 32 }");
+}
+
+#[test]
+fn it_finds_transitions() {
+    let data = [0x01, 0x00, 0x03, 0x00, 0x02, 0x01, 0x02, 0x00];
+    let logic = Logic { unit_size: 2, data: &data };
+    assert_eq!(logic.levels(0).collect::<Vec<_>>(), vec![true, true, false, false]);
+    assert_eq!(logic.transitions(0).collect::<Vec<_>>(), vec![(0, true), (2, false)]);
+    assert_eq!(logic.transitions(1).collect::<Vec<_>>(), vec![(0, false), (1, true)]);
+    assert_eq!(logic.transitions(8).collect::<Vec<_>>(), vec![(0, false), (2, true), (3, false)]);
+    assert_eq!(logic.transitions(16).count(), 0);
+}
+
+#[test]
+fn it_rejects_oversized_logic_packets() {
+    assert_eq!(logic_data_len(0), Some(0));
+    assert_eq!(logic_data_len(4096), Some(4096));
+    assert_eq!(logic_data_len(isize::max_value() as u64), Some(isize::max_value() as usize));
+    assert_eq!(logic_data_len(u64::max_value()), None);
+}
+
+#[test]
+fn it_times_samples() {
+    let data = [1, 2, 3];
+    let logic = Logic { unit_size: 1, data: &data };
+    let times: Vec<_> = logic.timed_samples(1_000, 10).collect();
+    assert_eq!(times, vec![
+        (Duration::from_millis(10), &data[0..1]),
+        (Duration::from_millis(11), &data[1..2]),
+        (Duration::from_millis(12), &data[2..3]),
+    ]);
+    assert!(logic.timed_samples(0, 5).all(|x| x.0 == Duration::from_secs(0)));
+    assert_eq!(Logic { unit_size: 0, data: &data }.timed_samples(1, 0).count(), 0);
+    assert_eq!(sample_time(3, 3_000_000_000), Duration::new(0, 1));
+}
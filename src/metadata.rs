@@ -0,0 +1,146 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use SampleRate;
+
+// Summary of a session's devices, as stored in a loaded session file. Fields the
+// session doesn't carry are left empty.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionMetadata {
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub samplerate: Option<SampleRate>,
+    pub total_samples: Option<u64>,
+    pub logic_channels: Vec<String>,
+    pub analog_channels: Vec<String>,
+}
+
+fn count(f: &mut fmt::Formatter, n: usize, what: &str) -> fmt::Result {
+    write!(f, "{} {} channel{}", n, what, if n == 1 { "" } else { "s" })
+}
+
+// e.g. "8 logic channels, 1000000 samples @ 1 MHz"
+impl fmt::Display for SessionMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.logic_channels.len(), self.analog_channels.len()) {
+            (0, 0) => f.write_str("no channels")?,
+            (logic, 0) => count(f, logic, "logic")?,
+            (0, analog) => count(f, analog, "analog")?,
+            (logic, analog) => {
+                count(f, logic, "logic")?;
+                f.write_str(", ")?;
+                count(f, analog, "analog")?;
+            }
+        }
+        if let Some(samples) = self.total_samples {
+            write!(f, ", {} samples", samples)?;
+        }
+        if let Some(samplerate) = self.samplerate {
+            write!(f, " @ {}", samplerate)?;
+        }
+        Ok(())
+    }
+}
+
+fn le16(b: &[u8]) -> u64 {
+    b[0] as u64 | (b[1] as u64) << 8
+}
+
+fn le32(b: &[u8]) -> u64 {
+    le16(b) | le16(&b[2..]) << 16
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Truncated zip directory")
+}
+
+// Names and uncompressed sizes of the entries in a zip archive, read from its
+// central directory (no data is decompressed). Zip64 archives aren't handled;
+// libsigrok writes captures in small chunks, so it never needs them.
+fn zip_entries<R: Read + Seek>(file: &mut R) -> io::Result<Vec<(String, u64)>> {
+    // The end-of-central-directory record is 22 bytes plus a comment of up to
+    // 64k, at the very end of the archive.
+    let len = file.seek(SeekFrom::End(0))?;
+    let tail_len = len.min(22 + 0xffff);
+    let mut tail = vec![0; tail_len as usize];
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    file.read_exact(&mut tail)?;
+    let eocd = (0..tail.len().saturating_sub(21)).rev()
+        .find(|&i| tail[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])
+        .map(|i| &tail[i..])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a zip archive"))?;
+    let (count, size, offset) = (le16(&eocd[10..]), le32(&eocd[12..]), le32(&eocd[16..]));
+
+    let mut dir = vec![0; size as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut dir)?;
+    let mut entries = vec![];
+    let mut rest = &dir[..];
+    for _ in 0..count {
+        if rest.len() < 46 || rest[..4] != [0x50, 0x4b, 0x01, 0x02] {
+            return Err(truncated());
+        }
+        let name_len = le16(&rest[28..]) as usize;
+        let entry_len = 46 + name_len + le16(&rest[30..]) as usize + le16(&rest[32..]) as usize;
+        if rest.len() < entry_len {
+            return Err(truncated());
+        }
+        let name = String::from_utf8_lossy(&rest[46..46 + name_len]).into_owned();
+        entries.push((name, le32(&rest[24..])));
+        rest = &rest[entry_len..];
+    }
+    Ok(entries)
+}
+
+// Samples stored for the first device of a session file. Logic data is kept in
+// "logic-1-<chunk>" entries (a single "logic-1" in older files) of `unit_size`
+// bytes per sample; analog-only captures store each channel as 32-bit floats
+// in "analog-1-<channel>-<chunk>". None if the file holds no sample data.
+pub(crate) fn stored_sample_count(path: &Path, unit_size: u64) -> io::Result<Option<u64>> {
+    let entries = zip_entries(&mut File::open(path)?)?;
+    let logic = entries.iter()
+        .filter(|x| x.0 == "logic-1" || x.0.starts_with("logic-1-"))
+        .map(|x| x.1)
+        .collect::<Vec<_>>();
+    if !logic.is_empty() {
+        if unit_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Session file has no logic unit size"));
+        }
+        return Ok(Some(logic.iter().sum::<u64>() / unit_size));
+    }
+    // Every analog channel holds the same number of samples; count the first.
+    let channel = |name: &str| name.split('-').nth(2).and_then(|x| x.parse::<u32>().ok());
+    let first = entries.iter()
+        .filter(|x| x.0.starts_with("analog-1-"))
+        .filter_map(|x| channel(&x.0))
+        .min();
+    Ok(first.map(|first| {
+        entries.iter()
+            .filter(|x| x.0.starts_with("analog-1-") && channel(&x.0) == Some(first))
+            .map(|x| x.1 / 4)
+            .sum()
+    }))
+}
+
+#[test]
+fn it_summarizes_metadata() {
+    let mut metadata = SessionMetadata::default();
+    assert_eq!(metadata.to_string(), "no channels");
+    metadata.logic_channels = (0..8).map(|x| format!("D{}", x)).collect();
+    metadata.total_samples = Some(1_000_000);
+    metadata.samplerate = Some(SampleRate(1_000_000));
+    assert_eq!(metadata.to_string(), "8 logic channels, 1000000 samples @ 1 MHz");
+    metadata.analog_channels = vec!["A0".to_string()];
+    metadata.total_samples = None;
+    assert_eq!(metadata.to_string(), "8 logic channels, 1 analog channel @ 1 MHz");
+}
+
+#[test]
+fn it_counts_stored_samples() {
+    // Two deflated chunks of 1000 and 500 one-byte samples.
+    let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/logic.sr"));
+    assert_eq!(stored_sample_count(path, 1).unwrap(), Some(1500));
+    assert_eq!(stored_sample_count(path, 2).unwrap(), Some(750));
+    assert!(stored_sample_count(Path::new("/nonexistent/capture.sr"), 1).is_err());
+}
@@ -0,0 +1,84 @@
+use std::io;
+use std::time::Duration;
+use logic::{sample_time, LogicCapture};
+use Datafeed;
+
+// Collects logic and analog packets from one mixed-signal capture, keeping the
+// two streams apart. Both count samples from the start of acquisition at the
+// device's samplerate, so logic sample n and analog sample n were taken at the
+// same time.
+#[derive(Debug, Clone, Default)]
+pub struct MixedCapture {
+    logic: LogicCapture,
+    analog: Vec<(String, Vec<f32>)>,
+    samplerate: u64,
+}
+
+impl MixedCapture {
+    pub fn new(samplerate: u64) -> MixedCapture {
+        MixedCapture {
+            samplerate: samplerate,
+            ..Default::default()
+        }
+    }
+
+    // Logic goes to a LogicCapture, analog is demuxed per channel the same way
+    // FrameCollector does (in the order channels first appear). Other packets
+    // are ignored.
+    pub fn push(&mut self, data: &Datafeed) -> io::Result<()> {
+        match data {
+            &Datafeed::Logic(ref logic) => self.logic.push(logic),
+            &Datafeed::Analog(ref analog) => analog.demux_into(&mut self.analog)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn logic(&self) -> &LogicCapture {
+        &self.logic
+    }
+
+    pub fn analog(&self) -> &[(String, Vec<f32>)] {
+        &self.analog
+    }
+
+    pub fn analog_channel(&self, name: &str) -> Option<&[f32]> {
+        self.analog.iter().find(|x| x.0 == name).map(|x| &x.1[..])
+    }
+
+    // Samples present in every stream that received data, i.e. the stretch of
+    // the timeline where all captured values are known.
+    pub fn len(&self) -> usize {
+        let logic = if self.logic.is_empty() { None } else { Some(self.logic.len()) };
+        logic.into_iter()
+            .chain(self.analog.iter().map(|x| x.1.len()))
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn samplerate(&self) -> u64 {
+        self.samplerate
+    }
+
+    // Offset of sample `n` from the start of acquisition, for either stream.
+    pub fn time_of_sample(&self, n: u64) -> Duration {
+        sample_time(n, self.samplerate)
+    }
+}
+
+#[test]
+fn it_collects_logic_on_the_timeline() {
+    use logic::Logic;
+
+    let data = [0x01, 0x02, 0x03];
+    let mut capture = MixedCapture::new(1_000);
+    capture.push(&Datafeed::Logic(Logic { unit_size: 1, data: &data })).unwrap();
+    capture.push(&Datafeed::End).unwrap();
+    assert_eq!(capture.len(), 3);
+    assert!(capture.analog().is_empty());
+    assert_eq!(capture.time_of_sample(2), Duration::from_millis(2));
+}
@@ -0,0 +1,132 @@
+use memmap2::MmapMut;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use {Datafeed, Logic};
+
+const INITIAL_CAPACITY: u64 = 1 << 20;
+
+// Writes logic samples straight into a memory-mapped file, for captures that
+// don't fit in memory. The file grows as packets arrive and is truncated to the
+// captured size by finish(), or on drop (where errors are ignored).
+pub struct MmapCapture {
+    file: File,
+    map: Option<MmapMut>,
+    capacity: u64,
+    len: u64,
+    unit_size: Option<u32>,
+}
+
+impl MmapCapture {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<MmapCapture> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let mut capture = MmapCapture {
+            file: file,
+            map: None,
+            capacity: 0,
+            len: 0,
+            unit_size: None,
+        };
+        capture.resize(INITIAL_CAPACITY)?;
+        Ok(capture)
+    }
+
+    pub fn push(&mut self, data: &Datafeed) -> io::Result<()> {
+        if let &Datafeed::Logic(Logic { unit_size, data }) = data {
+            match self.unit_size {
+                Some(size) if size != unit_size => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Logic unit size changed during capture"));
+                }
+                _ => self.unit_size = Some(unit_size),
+            }
+            let end = self.len + data.len() as u64;
+            if end > self.capacity {
+                let capacity = ::std::cmp::max(self.capacity * 2, end);
+                self.resize(capacity)?;
+            }
+            if let Some(ref mut map) = self.map {
+                map[self.len as usize..end as usize].copy_from_slice(data);
+            }
+            self.len = end;
+        }
+        Ok(())
+    }
+
+    // Flushes the mapping and trims the backing file to the captured bytes.
+    // Pushing more data afterwards grows the file again.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let len = self.len;
+        self.resize(len)
+    }
+
+    fn resize(&mut self, capacity: u64) -> io::Result<()> {
+        if let Some(map) = self.map.take() {
+            map.flush()?;
+        }
+        self.file.set_len(capacity)?;
+        self.capacity = capacity;
+        if capacity > 0 {
+            self.map = Some(unsafe { MmapMut::map_mut(&self.file)? });
+        }
+        Ok(())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self.map {
+            Some(ref map) => &map[..self.len as usize],
+            None => &[],
+        }
+    }
+
+    pub fn unit_size(&self) -> u32 {
+        self.unit_size.unwrap_or(0)
+    }
+
+    pub fn sample_count(&self) -> u64 {
+        match self.unit_size {
+            Some(size) if size > 0 => self.len / size as u64,
+            _ => 0,
+        }
+    }
+}
+
+impl Drop for MmapCapture {
+    fn drop(&mut self) {
+        if self.capacity != self.len {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[test]
+fn it_grows_and_trims_the_file() {
+    use std::env;
+    use std::fs;
+
+    let path = env::temp_dir().join(format!("sigrok-mmap-{}.bin", ::std::process::id()));
+    let mut capture = MmapCapture::create(&path).unwrap();
+    let packet: Vec<u8> = (0..64 * 1024).map(|x| x as u8).collect();
+    let packets = (INITIAL_CAPACITY as usize / packet.len()) + 3;
+    for _ in 0..packets {
+        capture.push(&Datafeed::Logic(Logic { unit_size: 2, data: &packet })).unwrap();
+    }
+    let len = (packets * packet.len()) as u64;
+    assert!(len > INITIAL_CAPACITY);
+    assert_eq!(capture.data().len() as u64, len);
+    assert_eq!(&capture.data()[..4], &[0, 1, 2, 3]);
+    assert_eq!(capture.sample_count(), len / 2);
+
+    let odd = Datafeed::Logic(Logic { unit_size: 1, data: &packet });
+    assert_eq!(capture.push(&odd).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(capture.data().len() as u64, len);
+
+    capture.finish().unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+    // Dropping without finish() trims as well.
+    let mut capture = MmapCapture::create(&path).unwrap();
+    capture.push(&Datafeed::Logic(Logic { unit_size: 2, data: &packet[..10] })).unwrap();
+    drop(capture);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 10);
+    fs::remove_file(&path).unwrap();
+}
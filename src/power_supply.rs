@@ -0,0 +1,137 @@
+use std::io;
+use {ConfigKey, ConfigOption, DriverChannelGroup, DriverInstance, Function};
+
+// Bench power supply controls over a device's config keys. Multi-output supplies
+// configure each output through its channel group; see for_group.
+#[derive(Debug, Clone)]
+pub struct PowerSupply {
+    device: DriverInstance,
+    group: Option<DriverChannelGroup>,
+}
+
+impl PowerSupply {
+    // Fails with InvalidInput if the device's driver isn't a power supply.
+    pub fn new(device: &DriverInstance) -> io::Result<PowerSupply> {
+        if !device.functions().contains(&Function::PowerSupply) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Device is not a power supply"));
+        }
+        Ok(PowerSupply {
+            device: device.clone(),
+            group: None,
+        })
+    }
+
+    pub fn for_group(device: &DriverInstance, group: &DriverChannelGroup) -> io::Result<PowerSupply> {
+        let mut supply = PowerSupply::new(device)?;
+        supply.group = Some(group.clone());
+        Ok(supply)
+    }
+
+    fn set(&self, config: &ConfigOption) -> io::Result<()> {
+        self.device.config_apply(self.group.as_ref(), config)
+    }
+
+    // Checks `value` against the range the driver lists for `key`, if it lists one.
+    fn set_in_range(&self, key: ConfigKey, value: f64, config: &ConfigOption) -> io::Result<()> {
+        if let Some((min, max)) = self.device.config_list_f64_range(self.group.as_ref(), key) {
+            if !(value >= min && value <= max) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("{:?} {} outside {}..{}", key, value, min, max)));
+            }
+        }
+        self.set(config)
+    }
+
+    pub fn set_voltage_target(&self, volts: f64) -> io::Result<()> {
+        self.set_in_range(ConfigKey::VoltageTarget, volts, &ConfigOption::VoltageTarget(volts))
+    }
+
+    pub fn set_current_limit(&self, amps: f64) -> io::Result<()> {
+        self.set_in_range(ConfigKey::CurrentLimit, amps, &ConfigOption::CurrentLimit(amps))
+    }
+
+    pub fn enable_output(&self, enabled: bool) -> io::Result<()> {
+        self.set(&ConfigOption::Enabled(enabled))
+    }
+
+    pub fn is_output_enabled(&self) -> Option<bool> {
+        self.device.config_get_bool(self.group.as_ref(), ConfigKey::Enabled)
+    }
+
+    // Measured output voltage and current.
+    pub fn read_voltage(&self) -> Option<f64> {
+        self.device.config_get_f64(self.group.as_ref(), ConfigKey::Voltage)
+    }
+
+    pub fn read_current(&self) -> Option<f64> {
+        self.device.config_get_f64(self.group.as_ref(), ConfigKey::Current)
+    }
+
+    pub fn set_over_voltage_protection(&self, enabled: bool) -> io::Result<()> {
+        self.set(&ConfigOption::OverVoltageProtectionEnabled(enabled))
+    }
+
+    pub fn set_over_voltage_threshold(&self, volts: f64) -> io::Result<()> {
+        self.set_in_range(ConfigKey::OverVoltageProtectionThreshold, volts,
+                          &ConfigOption::OverVoltageProtectionThreshold(volts))
+    }
+
+    // Whether over-voltage protection has tripped.
+    pub fn over_voltage_active(&self) -> Option<bool> {
+        self.device.config_get_bool(self.group.as_ref(), ConfigKey::OverVoltageProtectionActive)
+    }
+
+    pub fn set_over_current_protection(&self, enabled: bool) -> io::Result<()> {
+        self.set(&ConfigOption::OverCurrentProtectionEnabled(enabled))
+    }
+
+    pub fn set_over_current_threshold(&self, amps: f64) -> io::Result<()> {
+        self.set_in_range(ConfigKey::OverCurrentProtectionThreshold, amps,
+                          &ConfigOption::OverCurrentProtectionThreshold(amps))
+    }
+
+    // Whether over-current protection has tripped.
+    pub fn over_current_active(&self) -> Option<bool> {
+        self.device.config_get_bool(self.group.as_ref(), ConfigKey::OverCurrentProtectionActive)
+    }
+
+    // Reads every protection flag at once, for a status display.
+    pub fn protection_status(&self) -> ProtectionStatus {
+        let get = |key| self.device.config_get_bool(self.group.as_ref(), key);
+        ProtectionStatus {
+            over_voltage: get(ConfigKey::OverVoltageProtectionActive),
+            over_current: get(ConfigKey::OverCurrentProtectionActive),
+            over_temperature: get(ConfigKey::OverTemperatureProtectionActive),
+            under_voltage: get(ConfigKey::UnderVoltageConditionActive),
+        }
+    }
+}
+
+// Which protections have tripped. A field is None if the supply doesn't report
+// that condition.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtectionStatus {
+    pub over_voltage: Option<bool>,
+    pub over_current: Option<bool>,
+    pub over_temperature: Option<bool>,
+    pub under_voltage: Option<bool>,
+}
+
+impl ProtectionStatus {
+    // True if any reported protection has tripped.
+    pub fn any_active(&self) -> bool {
+        [self.over_voltage, self.over_current, self.over_temperature, self.under_voltage]
+            .iter()
+            .any(|x| *x == Some(true))
+    }
+}
+
+#[test]
+fn it_summarizes_protection_status() {
+    let mut status = ProtectionStatus::default();
+    assert!(!status.any_active());
+    status.over_current = Some(false);
+    assert!(!status.any_active());
+    status.under_voltage = Some(true);
+    assert!(status.any_active());
+}
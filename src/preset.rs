@@ -0,0 +1,123 @@
+use std::io;
+use toml::value::{Table, Value};
+use {ConfigDataType, ConfigKey, ConfigValue, DriverInstance, Mq, MqFlag};
+use num_rational::Ratio;
+
+impl DriverInstance {
+    // Current value of every key the device can both get and set, as a TOML
+    // document keyed by libsigrok's key ids (e.g. `samplerate = 1000000`).
+    // Pairs (ranges, rationals, measured quantities) are two-element arrays.
+    pub fn config_toml(&self) -> io::Result<String> {
+        let mut table = Table::new();
+        for (key, value) in self.config_snapshot() {
+            if let (Some(id), Some(value)) = (key.id(), to_toml(&value)) {
+                table.insert(id, value);
+            }
+        }
+        ::toml::to_string(&Value::Table(table))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    // Applies a document written by config_toml. Keys that are unknown, not
+    // settable on this device or of the wrong type are skipped, with a warning
+    // to the handler set by set_warning_handler.
+    pub fn apply_config_toml(&self, document: &str) -> io::Result<()> {
+        let table = match document.parse::<Value>() {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "Config preset is not a table")),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        for (id, value) in &table {
+            let key = match ConfigKey::from_id(id) {
+                Some(key) if self.config_abilities(key).set => key,
+                _ => {
+                    ::warn(format_args!("skipping config {:?}: not settable on this device", id));
+                    continue;
+                }
+            };
+            match key.data_type().and_then(|x| from_toml(value, x)) {
+                Some(value) => self.config_set_value(key, &value)?,
+                None => ::warn(format_args!("skipping config {:?}: unexpected value {}", id, value)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_toml(value: &ConfigValue) -> Option<Value> {
+    let pair = |a: Value, b: Value| Value::Array(vec![a, b]);
+    let int = |x: u64| if x <= i64::max_value() as u64 { Some(Value::Integer(x as i64)) } else { None };
+    Some(match value {
+        &ConfigValue::Bool(x) => Value::Boolean(x),
+        &ConfigValue::U64(x) => int(x)?,
+        &ConfigValue::F64(x) => Value::Float(x),
+        &ConfigValue::I32(x) => Value::Integer(x as i64),
+        &ConfigValue::String(ref x) => Value::String(x.clone()),
+        &ConfigValue::U64Range(low, high) => pair(int(low)?, int(high)?),
+        &ConfigValue::F64Range(low, high) => pair(Value::Float(low), Value::Float(high)),
+        &ConfigValue::Rational(ref x) => pair(int(*x.numer())?, int(*x.denom())?),
+        &ConfigValue::Mq(mq, ref flags) => {
+            let flags = flags.iter().fold(0u64, |acc, x| acc | x.to_raw() as u64);
+            pair(int(mq.to_raw() as u64)?, int(flags)?)
+        }
+    })
+}
+
+fn from_toml(value: &Value, data_type: ConfigDataType) -> Option<ConfigValue> {
+    let u64_of = |x: &Value| x.as_integer().and_then(|x| if x >= 0 { Some(x as u64) } else { None });
+    let f64_of = |x: &Value| x.as_float().or_else(|| x.as_integer().map(|x| x as f64));
+    let pair = match value.as_array() {
+        Some(items) if items.len() == 2 => Some((&items[0], &items[1])),
+        _ => None,
+    };
+    Some(match data_type {
+        ConfigDataType::Bool => ConfigValue::Bool(value.as_bool()?),
+        ConfigDataType::UInt64 => ConfigValue::U64(u64_of(value)?),
+        ConfigDataType::Float => ConfigValue::F64(f64_of(value)?),
+        ConfigDataType::Int32 => {
+            let x = value.as_integer()?;
+            if x < i32::min_value() as i64 || x > i32::max_value() as i64 {
+                return None;
+            }
+            ConfigValue::I32(x as i32)
+        }
+        ConfigDataType::String => ConfigValue::String(value.as_str()?.to_string()),
+        ConfigDataType::UInt64Range => {
+            let (low, high) = pair?;
+            ConfigValue::U64Range(u64_of(low)?, u64_of(high)?)
+        }
+        ConfigDataType::DoubleRange => {
+            let (low, high) = pair?;
+            ConfigValue::F64Range(f64_of(low)?, f64_of(high)?)
+        }
+        ConfigDataType::RationalPeriod | ConfigDataType::RationalVolt => {
+            let (p, q) = pair?;
+            ConfigValue::Rational(Ratio::new_raw(u64_of(p)?, u64_of(q)?))
+        }
+        ConfigDataType::Mq => {
+            let (mq, flags) = pair?;
+            ConfigValue::Mq(Mq::from_raw(u64_of(mq)? as u32)?, MqFlag::from_bits(u64_of(flags)?))
+        }
+        ConfigDataType::KeyValue => return None,
+    })
+}
+
+#[test]
+fn it_round_trips_toml_values() {
+    let values = vec![
+        (ConfigValue::Bool(true), ConfigDataType::Bool),
+        (ConfigValue::U64(1_000_000), ConfigDataType::UInt64),
+        (ConfigValue::F64(2.5), ConfigDataType::Float),
+        (ConfigValue::I32(-3), ConfigDataType::Int32),
+        (ConfigValue::String("sigrok".to_string()), ConfigDataType::String),
+        (ConfigValue::U64Range(10, 20), ConfigDataType::UInt64Range),
+        (ConfigValue::F64Range(0.5, 1.5), ConfigDataType::DoubleRange),
+        (ConfigValue::Rational(Ratio::new_raw(1, 1000)), ConfigDataType::RationalPeriod),
+    ];
+    for (value, data_type) in values {
+        assert_eq!(from_toml(&to_toml(&value).unwrap(), data_type), Some(value));
+    }
+    assert_eq!(to_toml(&ConfigValue::U64(u64::max_value())), None);
+    assert_eq!(from_toml(&Value::Integer(-1), ConfigDataType::UInt64), None);
+    assert_eq!(from_toml(&Value::Integer(5), ConfigDataType::Float), Some(ConfigValue::F64(5.0)));
+}
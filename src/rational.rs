@@ -0,0 +1,97 @@
+use num_rational::Ratio;
+use std::time::Duration;
+use ConfigOption;
+
+// Lossy conversion of sigrok's rationals (config values, analog scale and
+// offset) to floating point. A zero denominator gives NaN.
+pub trait ToF64 {
+    fn to_f64(&self) -> f64;
+}
+
+impl ToF64 for Ratio<u64> {
+    fn to_f64(&self) -> f64 {
+        match *self.denom() {
+            0 => ::std::f64::NAN,
+            q => *self.numer() as f64 / q as f64,
+        }
+    }
+}
+
+impl ToF64 for Ratio<i64> {
+    fn to_f64(&self) -> f64 {
+        match *self.denom() {
+            0 => ::std::f64::NAN,
+            q => *self.numer() as f64 / q as f64,
+        }
+    }
+}
+
+// Time per horizontal division, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timebase(pub Ratio<u64>);
+
+impl Timebase {
+    // None if the denominator is zero.
+    pub fn as_duration(&self) -> Option<Duration> {
+        let (p, q) = (*self.0.numer(), *self.0.denom());
+        if q == 0 {
+            return None;
+        }
+        let nanos = (p % q) as u128 * 1_000_000_000 / q as u128;
+        Some(Duration::new(p / q, nanos as u32))
+    }
+
+    // Seconds per division; NaN if the denominator is zero.
+    pub fn as_secs(&self) -> f64 {
+        self.0.to_f64()
+    }
+}
+
+impl ToF64 for Timebase {
+    fn to_f64(&self) -> f64 {
+        self.as_secs()
+    }
+}
+
+impl From<Timebase> for ConfigOption {
+    fn from(timebase: Timebase) -> ConfigOption {
+        ConfigOption::Timebase(timebase.0)
+    }
+}
+
+// Volts per vertical division.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Vdiv(pub Ratio<u64>);
+
+impl Vdiv {
+    // NaN if the denominator is zero.
+    pub fn as_volts(&self) -> f64 {
+        self.0.to_f64()
+    }
+}
+
+impl ToF64 for Vdiv {
+    fn to_f64(&self) -> f64 {
+        self.as_volts()
+    }
+}
+
+impl From<Vdiv> for ConfigOption {
+    fn from(vdiv: Vdiv) -> ConfigOption {
+        ConfigOption::Vdiv(vdiv.0)
+    }
+}
+
+#[test]
+fn it_converts_scope_rationals() {
+    assert_eq!(Timebase(Ratio::new_raw(1, 1000)).as_duration(), Some(Duration::from_millis(1)));
+    assert_eq!(Timebase(Ratio::new_raw(500, 1_000_000_000)).as_duration(), Some(Duration::from_nanos(500)));
+    assert_eq!(Timebase(Ratio::new_raw(10, 1)).as_duration(), Some(Duration::from_secs(10)));
+    assert_eq!(Timebase(Ratio::new_raw(1, 0)).as_duration(), None);
+    assert_eq!(Vdiv(Ratio::new_raw(1, 2)).as_volts(), 0.5);
+    assert_eq!(Vdiv(Ratio::new_raw(5, 1)).as_volts(), 5.0);
+    assert!(Vdiv(Ratio::new_raw(1, 0)).as_volts().is_nan());
+    assert_eq!(Timebase(Ratio::new_raw(1, 4)).to_f64(), 0.25);
+    assert_eq!(Ratio::<i64>::new_raw(-3, 2).to_f64(), -1.5);
+    assert!(Ratio::<i64>::new_raw(1, 0).to_f64().is_nan());
+}
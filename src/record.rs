@@ -0,0 +1,120 @@
+use std::io;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+use Datafeed;
+
+// A datafeed packet copied out of libsigrok's buffers, so it can outlive the
+// callback, e.g. to be recorded or sent to another process.
+//
+// With the `serde` feature it serializes as an object tagged by "type":
+//   {"type":"header","feed_version":1,"start_sec":...,"start_nsec":...}
+//   {"type":"logic","unit_size":1,"data":"<base64>"}
+//   {"type":"analog","channels":["A0"],"mq":"voltage","unit":"volt","mq_flags":0,"values":[...]}
+//   {"type":"trigger","sample":1024}
+//   {"type":"frame_begin"}, {"type":"frame_end"}, {"type":"end"}, {"type":"aborted"}
+//   {"type":"unknown","packet_type":10}
+// Logic data stays packed (see Logic). Analog values are decoded and
+// interleaved across `channels`; mq and unit use Mq::id and Unit::id.
+// Fields are only ever added to this format.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum OwnedDatafeed {
+    Header {
+        feed_version: i32,
+        start_sec: i64,
+        start_nsec: i32,
+    },
+    Logic {
+        unit_size: u32,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_base64"))]
+        data: Vec<u8>,
+    },
+    Analog {
+        channels: Vec<String>,
+        mq: Option<&'static str>,
+        unit: Option<&'static str>,
+        mq_flags: u64,
+        values: Vec<f32>,
+    },
+    Trigger {
+        sample: Option<u64>,
+    },
+    FrameBegin,
+    FrameEnd,
+    End,
+    Aborted,
+    Unknown {
+        packet_type: u16,
+    },
+}
+
+impl OwnedDatafeed {
+    // Fails only if an analog packet can't be decoded.
+    pub fn from_datafeed(data: &Datafeed) -> io::Result<OwnedDatafeed> {
+        Ok(match data {
+            &Datafeed::Header { feed_version, start_time } => OwnedDatafeed::Header {
+                feed_version: feed_version,
+                start_sec: start_time.sec,
+                start_nsec: start_time.nsec,
+            },
+            &Datafeed::Logic(ref logic) => OwnedDatafeed::Logic {
+                unit_size: logic.unit_size,
+                data: logic.data.to_vec(),
+            },
+            &Datafeed::Analog(ref analog) => OwnedDatafeed::Analog {
+                channels: analog.channels.iter().map(|x| x.name()).collect(),
+                mq: analog.mq.map(|x| x.id()),
+                unit: analog.unit.map(|x| x.id()),
+                mq_flags: analog.mq_flags,
+                values: analog.to_float()?,
+            },
+            &Datafeed::Trigger { sample } => OwnedDatafeed::Trigger { sample: sample },
+            &Datafeed::FrameBegin => OwnedDatafeed::FrameBegin,
+            &Datafeed::FrameEnd => OwnedDatafeed::FrameEnd,
+            &Datafeed::End => OwnedDatafeed::End,
+            &Datafeed::Aborted => OwnedDatafeed::Aborted,
+            &Datafeed::Unknown { packet_type } => OwnedDatafeed::Unknown { packet_type: packet_type },
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_base64<S: Serializer>(data: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64(data))
+}
+
+// Standard alphabet with padding (RFC 4648).
+#[cfg(any(test, feature = "serde"))]
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn it_encodes_base64() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(&[0xff, 0x00, 0x80, 0x01]), "/wCAAQ==");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_serializes_tagged_packets() {
+    let logic = OwnedDatafeed::Logic { unit_size: 1, data: vec![0xff, 0x00] };
+    assert_eq!(::serde_json::to_string(&logic).unwrap(), r#"{"type":"logic","unit_size":1,"data":"/wA="}"#);
+    assert_eq!(::serde_json::to_string(&OwnedDatafeed::FrameEnd).unwrap(), r#"{"type":"frame_end"}"#);
+}
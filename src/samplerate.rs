@@ -0,0 +1,118 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use ConfigOption;
+
+const PREFIXES: [(u64, &'static str); 3] = [
+    (1_000_000_000, "G"),
+    (1_000_000, "M"),
+    (1_000, "k"),
+];
+
+// A samplerate in Hz. Formats like sigrok-cli ("1 MHz", "1.5 kHz") and parses
+// "1M", "1.5k", "250 kHz" or a plain number of Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SampleRate(pub u64);
+
+impl SampleRate {
+    pub fn hz(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for SampleRate {
+    fn from(hz: u64) -> SampleRate {
+        SampleRate(hz)
+    }
+}
+
+impl From<SampleRate> for u64 {
+    fn from(rate: SampleRate) -> u64 {
+        rate.0
+    }
+}
+
+impl From<SampleRate> for ConfigOption {
+    fn from(rate: SampleRate) -> ConfigOption {
+        ConfigOption::SampleRate(rate.0)
+    }
+}
+
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(scale, prefix) in PREFIXES.iter() {
+            if self.0 >= scale {
+                let frac = format!("{:09}", (self.0 % scale) * (1_000_000_000 / scale));
+                let frac = frac.trim_end_matches('0');
+                return if frac.is_empty() {
+                    write!(f, "{} {}Hz", self.0 / scale, prefix)
+                } else {
+                    write!(f, "{}.{} {}Hz", self.0 / scale, frac, prefix)
+                };
+            }
+        }
+        write!(f, "{} Hz", self.0)
+    }
+}
+
+impl FromStr for SampleRate {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<SampleRate> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid samplerate {:?}", s));
+
+        let mut value = s.trim();
+        if value.ends_with("Hz") || value.ends_with("hz") {
+            value = value[..value.len() - 2].trim_end();
+        }
+        let (number, scale) = match value.chars().last() {
+            Some('k') | Some('K') => (&value[..value.len() - 1], 1_000),
+            Some('m') | Some('M') => (&value[..value.len() - 1], 1_000_000),
+            Some('g') | Some('G') => (&value[..value.len() - 1], 1_000_000_000),
+            _ => (value, 1),
+        };
+        let number = number.trim_end();
+
+        let (int, frac) = match number.find('.') {
+            Some(i) => (&number[..i], &number[i + 1..]),
+            None => (number, ""),
+        };
+        if int.is_empty() || !int.chars().all(|c| c.is_ascii_digit()) || !frac.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let mut hz = int.parse::<u64>().ok().and_then(|x| x.checked_mul(scale)).ok_or_else(&invalid)?;
+        let mut place = scale;
+        for digit in frac.chars() {
+            let digit = digit.to_digit(10).unwrap() as u64;
+            if place % 10 != 0 {
+                if digit != 0 {
+                    return Err(invalid());
+                }
+                continue;
+            }
+            place /= 10;
+            hz = hz.checked_add(digit * place).ok_or_else(&invalid)?;
+        }
+        Ok(SampleRate(hz))
+    }
+}
+
+#[test]
+fn it_formats_samplerates() {
+    assert_eq!(SampleRate(1_000_000).to_string(), "1 MHz");
+    assert_eq!(SampleRate(1_500).to_string(), "1.5 kHz");
+    assert_eq!(SampleRate(20).to_string(), "20 Hz");
+    assert_eq!(SampleRate(2_000_000_000).to_string(), "2 GHz");
+}
+
+#[test]
+fn it_parses_samplerates() {
+    assert_eq!("1M".parse::<SampleRate>().unwrap(), SampleRate(1_000_000));
+    assert_eq!("1.5k".parse::<SampleRate>().unwrap(), SampleRate(1_500));
+    assert_eq!("250 kHz".parse::<SampleRate>().unwrap(), SampleRate(250_000));
+    assert_eq!("48000".parse::<SampleRate>().unwrap(), SampleRate(48_000));
+    assert_eq!("1 MHz".parse::<SampleRate>().unwrap().to_string(), "1 MHz");
+    assert!("1.5".parse::<SampleRate>().is_err());
+    assert!("fast".parse::<SampleRate>().is_err());
+    assert!("".parse::<SampleRate>().is_err());
+}
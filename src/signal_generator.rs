@@ -0,0 +1,71 @@
+use std::io;
+use {ConfigKey, ConfigOption, DriverChannelGroup, DriverInstance, Function};
+
+// Function generator controls over a device's config keys. Drivers with several
+// outputs configure each through its channel group; see for_group.
+#[derive(Debug, Clone)]
+pub struct SignalGenerator {
+    device: DriverInstance,
+    group: Option<DriverChannelGroup>,
+}
+
+impl SignalGenerator {
+    // Fails with InvalidInput if the device's driver isn't a signal generator.
+    pub fn new(device: &DriverInstance) -> io::Result<SignalGenerator> {
+        if !device.functions().contains(&Function::SignalGenerator) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Device is not a signal generator"));
+        }
+        Ok(SignalGenerator {
+            device: device.clone(),
+            group: None,
+        })
+    }
+
+    pub fn for_group(device: &DriverInstance, group: &DriverChannelGroup) -> io::Result<SignalGenerator> {
+        let mut generator = SignalGenerator::new(device)?;
+        generator.group = Some(group.clone());
+        Ok(generator)
+    }
+
+    fn set(&self, config: &ConfigOption) -> io::Result<()> {
+        self.device.config_apply(self.group.as_ref(), config)
+    }
+
+    fn can_set(&self, key: ConfigKey) -> bool {
+        self.device.abilities(self.group.as_ref(), key).set
+    }
+
+    // Some drivers take a separate target setting and report the actual
+    // frequency through OutputFrequency; prefer the target when there is one.
+    pub fn set_frequency(&self, hz: f64) -> io::Result<()> {
+        if self.can_set(ConfigKey::OutputFrequencyTarget) {
+            self.set(&ConfigOption::OutputFrequencyTarget(hz))
+        } else {
+            self.set(&ConfigOption::OutputFrequency(hz))
+        }
+    }
+
+    pub fn frequency(&self) -> Option<f64> {
+        self.device.config_get_f64(self.group.as_ref(), ConfigKey::OutputFrequency)
+    }
+
+    pub fn set_amplitude(&self, volts: f64) -> io::Result<()> {
+        self.set(&ConfigOption::Amplitude(volts))
+    }
+
+    pub fn set_offset(&self, volts: f64) -> io::Result<()> {
+        self.set(&ConfigOption::Offset(volts))
+    }
+
+    // Drivers expose the waveform either as the pattern mode or as the device
+    // mode, using their own names ("Sine", "Square", ...).
+    pub fn set_waveform(&self, waveform: &str) -> io::Result<()> {
+        if self.can_set(ConfigKey::PatternMode) {
+            self.set(&ConfigOption::PatternMode(waveform.to_string()))
+        } else if self.can_set(ConfigKey::DeviceMode) {
+            self.set(&ConfigOption::DeviceMode(waveform.to_string()))
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "Device has no settable waveform"))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::io;
+use {Sigrok, Session, DriverInstance, ConfigOption};
+
+// Settings applied to the demo device by demo_device(). Unset fields keep the
+// driver's defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemoOptions {
+    pub samplerate: Option<u64>,
+    // Pattern for the logic channels, e.g. "sigrok", "random" or "incremental".
+    pub pattern: Option<String>,
+    // Peak-to-peak amplitude of the analog channels, in volts.
+    pub amplitude: Option<f64>,
+}
+
+impl Default for DemoOptions {
+    fn default() -> DemoOptions {
+        DemoOptions {
+            samplerate: Some(1_000_000),
+            pattern: None,
+            amplitude: None,
+        }
+    }
+}
+
+// A demo device attached to its own session, for tests that need a datafeed
+// without real hardware. Fields drop in order, so the context outlives the
+// session and device that refer to it.
+pub struct DemoDevice {
+    pub session: Session,
+    pub device: DriverInstance,
+    pub ctx: Sigrok,
+}
+
+// Scans the demo driver and attaches its first device to a new session. Fails
+// with NotFound if libsigrok was built without the demo driver.
+pub fn demo_device(options: &DemoOptions) -> io::Result<DemoDevice> {
+    let mut ctx = Sigrok::new()?;
+    let driver = ctx.drivers().into_iter().find(|x| x.name() == "demo")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "libsigrok has no demo driver"))?;
+    let demo = ctx.init_driver(&driver)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Could not initialize demo driver"))?;
+    let (session, devices) = Session::from_scan(&mut ctx, &demo, &[])?;
+    let device = devices.into_iter().next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Demo driver found no device"))?;
+
+    if let Some(rate) = options.samplerate {
+        device.config_set_checked(&ConfigOption::SampleRate(rate))?;
+    }
+    for group in device.channel_groups() {
+        let channels = group.channels();
+        if let Some(ref pattern) = options.pattern {
+            if !channels.is_empty() && channels.iter().all(|x| x.is_logic()) {
+                device.config_apply(Some(&group), &ConfigOption::PatternMode(pattern.clone()))?;
+            }
+        }
+        if let Some(amplitude) = options.amplitude {
+            // Only per-channel analog groups take an amplitude.
+            if channels.len() == 1 && channels[0].is_analog() {
+                device.config_apply(Some(&group), &ConfigOption::Amplitude(amplitude))?;
+            }
+        }
+    }
+
+    Ok(DemoDevice {
+        session,
+        device,
+        ctx,
+    })
+}
+
+// A demo device with all of its logic and analog channels enabled, so that
+// captures (e.g. DriverInstance::capture_mixed) carry both kinds of data.
+pub fn mixed_signal_demo(options: &DemoOptions) -> io::Result<DemoDevice> {
+    let demo = demo_device(options)?;
+    for channel in demo.device.channels() {
+        channel.set_enabled(true)?;
+    }
+    Ok(demo)
+}
+
+// For tests that need the demo driver: None (skip the test) if libsigrok was
+// built without it, but any other setup error fails the test.
+pub fn skip_if_missing(result: io::Result<DemoDevice>) -> Option<DemoDevice> {
+    match result {
+        Ok(demo) => Some(demo),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => panic!("Could not set up demo device: {}", err),
+    }
+}
@@ -0,0 +1,372 @@
+use sigrok_sys::{Struct_sr_trigger, sr_trigger_new, sr_trigger_free, sr_trigger_stage_add};
+use sigrok_sys::sr_trigger_match_add;
+use std::fmt;
+use std::io;
+use std::iter::FromIterator;
+use std::ptr;
+use {DriverChannel, DriverChannelGroup, DriverInstance, ConfigKey, TriggerType, Unit};
+
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    channel: DriverChannel,
+    kind: TriggerType,
+    value: f32,
+}
+
+impl Trigger {
+    pub fn new(channel: &DriverChannel, kind: TriggerType) -> Trigger {
+        Trigger {
+            channel: channel.clone(),
+            kind: kind,
+            value: 0.0,
+        }
+    }
+
+    // Builds an Over/Under trigger on an analog channel. Voltage levels are checked
+    // against the SR_CONF_VOLTAGE_THRESHOLD range listed for the channel's own
+    // channel group, or the device's when the group lists none.
+    pub fn analog(channel: &DriverChannel, kind: TriggerType, value: f32, unit: Unit) -> io::Result<Trigger> {
+        if !channel.is_analog() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Analog trigger requires an analog channel"));
+        }
+        if kind != TriggerType::Over && kind != TriggerType::Under {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Analog trigger must be Over or Under"));
+        }
+        if unit == Unit::Volt {
+            if let Some((low, high)) = voltage_range(channel) {
+                if (value as f64) < low || (value as f64) > high {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "Trigger level is outside the channel's voltage range"));
+                }
+            }
+        }
+        Ok(Trigger {
+            channel: channel.clone(),
+            kind: kind,
+            value: value,
+        })
+    }
+
+    // Builds a trigger on a channel group, e.g. a scope's "CH1". The group
+    // resolves to its first channel (in the group's own order) that supports
+    // `kind`; for a scope channel group that's the single analog channel.
+    // `value` is the level for Over/Under triggers and ignored otherwise.
+    pub fn for_group(group: &DriverChannelGroup, kind: TriggerType, value: f32) -> io::Result<Trigger> {
+        let channel = group.channels().into_iter()
+            .find(|x| x.supported_triggers().contains(&kind))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("No channel in {:?} supports {:?} triggers", group.name(), kind)))?;
+        let analog = kind == TriggerType::Over || kind == TriggerType::Under;
+        Ok(Trigger {
+            channel: channel,
+            kind: kind,
+            value: if analog { value } else { 0.0 },
+        })
+    }
+
+    pub fn channel(&self) -> &DriverChannel {
+        &self.channel
+    }
+
+    pub fn kind(&self) -> TriggerType {
+        self.kind
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    // Parses one sigrok-cli style match, e.g. "D0=r", resolving the channel
+    // on `device`. Over/Under take their level after the code ("A0=o1.5").
+    pub fn from_string(device: &DriverInstance, s: &str) -> io::Result<Trigger> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid trigger {:?}", s));
+        let mut parts = s.trim().splitn(2, '=');
+        let name = parts.next().unwrap();
+        let spec = parts.next().ok_or_else(&invalid)?;
+        let channel = device.channel_by_name(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No channel named {:?}", name)))?;
+        let (code, level) = spec.split_at(spec.chars().next().map_or(0, |x| x.len_utf8()));
+        let kind: TriggerType = code.parse()?;
+        let value = match kind {
+            TriggerType::Over | TriggerType::Under => level.parse().map_err(|_| invalid())?,
+            _ if level.is_empty() => 0.0,
+            _ => return Err(invalid()),
+        };
+        Ok(Trigger {
+            channel: channel,
+            kind: kind,
+            value: value,
+        })
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.channel.name(), self.kind)?;
+        match self.kind {
+            TriggerType::Over | TriggerType::Under => write!(f, "{}", self.value),
+            _ => Ok(()),
+        }
+    }
+}
+
+// One stage of a trigger. libsigrok doesn't store stage names, so the optional
+// label only exists on this side.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerStage {
+    pub name: Option<String>,
+    pub triggers: Vec<Trigger>,
+}
+
+impl TriggerStage {
+    pub fn new(triggers: Vec<Trigger>) -> TriggerStage {
+        TriggerStage {
+            name: None,
+            triggers: triggers,
+        }
+    }
+
+    pub fn named(name: &str, triggers: Vec<Trigger>) -> TriggerStage {
+        TriggerStage {
+            name: Some(name.to_string()),
+            triggers: triggers,
+        }
+    }
+
+    // Parses the Display form, e.g. "address match: D0=r,D1=1". The name is
+    // everything before the last ':', so it may contain colons itself.
+    pub fn from_string(device: &DriverInstance, s: &str) -> io::Result<TriggerStage> {
+        let (name, spec) = match s.rfind(':') {
+            Some(i) => (Some(s[..i].trim().to_string()), &s[i + 1..]),
+            None => (None, s),
+        };
+        let triggers = spec.split(',')
+            .filter(|x| !x.trim().is_empty())
+            .map(|x| Trigger::from_string(device, x))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(TriggerStage {
+            name: name,
+            triggers: triggers,
+        })
+    }
+}
+
+// e.g. "address match: D0=r,D1=1", or just "D0=r,D1=1" without a name.
+impl fmt::Display for TriggerStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref name) = self.name {
+            write!(f, "{}: ", name)?;
+        }
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", trigger)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Triggers {
+    pub(crate) context: *mut Struct_sr_trigger,
+    stages: Vec<TriggerStage>,
+}
+
+impl Triggers {
+    pub fn new<I, S>(stages: I) -> io::Result<Triggers>
+        where I: IntoIterator<Item = S>, S: IntoIterator<Item = Trigger>
+    {
+        Triggers::from_stages(stages.into_iter().map(|x| TriggerStage::new(x.into_iter().collect())).collect())
+    }
+
+    pub fn from_stages(stages: Vec<TriggerStage>) -> io::Result<Triggers> {
+        unsafe {
+            let mut triggers = Triggers {
+                context: sr_trigger_new(ptr::null()),
+                stages: vec![],
+            };
+            for stage in &stages {
+                let stage_context = sr_trigger_stage_add(triggers.context);
+                for trigger in &stage.triggers {
+                    let res = sr_trigger_match_add(stage_context, trigger.channel.context, trigger.kind.to_raw() as i32, trigger.value);
+                    if res != 0 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Could not add trigger match"));
+                    }
+                }
+            }
+            triggers.stages = stages;
+            Ok(triggers)
+        }
+    }
+
+    // A single stage holding all of `triggers`, the common case. This is the
+    // fallible form of `iter.collect::<Triggers>()`.
+    pub fn try_from_iter<I: IntoIterator<Item = Trigger>>(triggers: I) -> io::Result<Triggers> {
+        Triggers::from_stages(vec![TriggerStage::new(triggers.into_iter().collect())])
+    }
+
+    // The stages as they were built, including their names.
+    pub fn stages(&self) -> &[TriggerStage] {
+        &self.stages
+    }
+
+    // Parses stages separated by ';' (see TriggerStage::from_string), so stage
+    // names can't contain ';'.
+    pub fn from_string(device: &DriverInstance, s: &str) -> io::Result<Triggers> {
+        let stages = s.split(';')
+            .map(|x| TriggerStage::from_string(device, x))
+            .collect::<io::Result<Vec<_>>>()?;
+        Triggers::from_stages(stages)
+    }
+}
+
+// e.g. "start condition: D0=f; address match: D1=r,D2=1".
+impl fmt::Display for Triggers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", stage)?;
+        }
+        Ok(())
+    }
+}
+
+// Range of trigger levels for `channel`. Groups are tried smallest first, so a
+// scope's per-channel group wins over any group spanning several channels.
+fn voltage_range(channel: &DriverChannel) -> Option<(f64, f64)> {
+    let device = channel.device();
+    let mut groups: Vec<DriverChannelGroup> = device.channel_groups().into_iter()
+        .filter(|x| x.channels().contains(channel))
+        .collect();
+    groups.sort_by_key(|x| x.channels().len());
+    groups.iter()
+        .filter_map(|x| device.config_list_f64_range(Some(x), ConfigKey::VoltageThreshold))
+        .next()
+        .or_else(|| device.config_list_f64_range(None, ConfigKey::VoltageThreshold))
+}
+
+// Collecting triggers builds a single stage. libsigrok rejects a match that
+// doesn't suit its channel (e.g. Over on a logic channel), and collecting into
+// Triggers panics on that; collect into io::Result<Triggers> or use
+// Triggers::try_from_iter to handle it instead.
+impl FromIterator<Trigger> for Triggers {
+    fn from_iter<I: IntoIterator<Item = Trigger>>(triggers: I) -> Triggers {
+        Triggers::try_from_iter(triggers).expect("Could not build trigger stage")
+    }
+}
+
+impl FromIterator<Trigger> for io::Result<Triggers> {
+    fn from_iter<I: IntoIterator<Item = Trigger>>(triggers: I) -> io::Result<Triggers> {
+        Triggers::try_from_iter(triggers)
+    }
+}
+
+// Collects trigger stages for Session::start_with. Only channels of devices
+// attached to the session are accepted.
+pub struct TriggerBuilder {
+    devices: Vec<DriverInstance>,
+    stages: Vec<TriggerStage>,
+}
+
+impl TriggerBuilder {
+    pub(crate) fn new(devices: Vec<DriverInstance>) -> TriggerBuilder {
+        TriggerBuilder {
+            devices: devices,
+            stages: vec![],
+        }
+    }
+
+    pub fn devices(&self) -> &[DriverInstance] {
+        &self.devices
+    }
+
+    pub fn channel(&self, name: &str) -> Option<DriverChannel> {
+        self.devices.iter()
+            .flat_map(|x| x.channels())
+            .find(|x| x.name() == name)
+    }
+
+    // Starts a new stage; following triggers are added to it.
+    pub fn stage(&mut self) -> &mut TriggerBuilder {
+        self.stages.push(TriggerStage::default());
+        self
+    }
+
+    // Starts a new stage with a label, e.g. "address match".
+    pub fn named_stage(&mut self, name: &str) -> &mut TriggerBuilder {
+        self.stages.push(TriggerStage::named(name, vec![]));
+        self
+    }
+
+    pub fn add(&mut self, trigger: Trigger) -> io::Result<&mut TriggerBuilder> {
+        let device = trigger.channel.device();
+        if !self.devices.iter().any(|x| x.context == device.context) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Trigger channel doesn't belong to a session device"));
+        }
+        if self.stages.is_empty() {
+            self.stages.push(TriggerStage::default());
+        }
+        self.stages.last_mut().unwrap().triggers.push(trigger);
+        Ok(self)
+    }
+
+    pub(crate) fn build(self) -> io::Result<Option<Triggers>> {
+        if self.stages.iter().all(|x| x.triggers.is_empty()) {
+            return Ok(None);
+        }
+        Triggers::from_stages(self.stages.into_iter().filter(|x| !x.triggers.is_empty()).collect()).map(Some)
+    }
+}
+
+impl Drop for Triggers {
+    fn drop(&mut self) {
+        unsafe {
+            sr_trigger_free(self.context);
+        }
+    }
+}
+
+#[test]
+fn it_rejects_analog_levels_out_of_range() {
+    if let Some(demo) = ::testing::skip_if_missing(::testing::demo_device(&Default::default())) {
+        for channel in demo.device.channels() {
+            if !channel.is_analog() {
+                assert!(Trigger::analog(&channel, TriggerType::Over, 0.0, Unit::Volt).is_err());
+            } else if let Some((low, high)) = voltage_range(&channel) {
+                let err = Trigger::analog(&channel, TriggerType::Over, (high + 1.0) as f32, Unit::Volt).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+                assert!(Trigger::analog(&channel, TriggerType::Under, (low - 1.0) as f32, Unit::Volt).is_err());
+                assert!(Trigger::analog(&channel, TriggerType::Over, ((low + high) / 2.0) as f32, Unit::Volt).is_ok());
+            }
+        }
+    }
+}
+
+#[test]
+fn it_round_trips_stage_names_through_strings() {
+    if let Some(demo) = ::testing::skip_if_missing(::testing::demo_device(&Default::default())) {
+        let device = &demo.device;
+        let d0 = device.channel_by_name("D0").unwrap();
+        let d1 = device.channel_by_name("D1").unwrap();
+        let triggers = Triggers::from_stages(vec![
+            TriggerStage::named("start condition", vec![Trigger::new(&d0, TriggerType::Falling)]),
+            TriggerStage::named("address match", vec![Trigger::new(&d0, TriggerType::Rising), Trigger::new(&d1, TriggerType::One)]),
+            TriggerStage::new(vec![Trigger::new(&d1, TriggerType::Zero)]),
+        ]).unwrap();
+        let s = triggers.to_string();
+        assert_eq!(s, "start condition: D0=f; address match: D0=r,D1=1; D1=0");
+
+        let parsed = Triggers::from_string(device, &s).unwrap();
+        let names: Vec<Option<&str>> = parsed.stages().iter().map(|x| x.name.as_ref().map(|x| &x[..])).collect();
+        assert_eq!(names, vec![Some("start condition"), Some("address match"), None]);
+        assert_eq!(parsed.stages()[1].triggers[1].channel(), &d1);
+        assert_eq!(parsed.stages()[1].triggers[1].kind(), TriggerType::One);
+        assert_eq!(parsed.to_string(), s);
+
+        assert!(TriggerStage::from_string(device, "D0=x").is_err());
+        assert!(TriggerStage::from_string(device, "D0=r1.5").is_err());
+        assert_eq!(TriggerStage::from_string(device, "no-such-channel=r").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,49 @@
+// USB coordinates in the two forms the Connection scan option accepts:
+// "vendor.product" as four hex digits each, or decimal "bus.address".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UsbAddress {
+    Id {
+        vendor: u16,
+        product: u16,
+    },
+    Address {
+        bus: u8,
+        address: u8,
+    },
+}
+
+impl UsbAddress {
+    // Like libsigrok, the VID.PID form is tried before bus.address.
+    pub fn parse(conn: &str) -> Option<UsbAddress> {
+        let mut parts = conn.split('.');
+        let (left, right) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(left), Some(right), None) => (left, right),
+            _ => return None,
+        };
+        let is_hex4 = |s: &str| s.len() == 4 && s.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hex4(left) && is_hex4(right) {
+            return Some(UsbAddress::Id {
+                vendor: u16::from_str_radix(left, 16).ok()?,
+                product: u16::from_str_radix(right, 16).ok()?,
+            });
+        }
+        let is_dec = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+        if is_dec(left) && is_dec(right) {
+            return Some(UsbAddress::Address {
+                bus: left.parse().ok()?,
+                address: right.parse().ok()?,
+            });
+        }
+        None
+    }
+}
+
+#[test]
+fn it_parses_usb_addresses() {
+    assert_eq!(UsbAddress::parse("1d6b.0002"), Some(UsbAddress::Id { vendor: 0x1d6b, product: 0x0002 }));
+    assert_eq!(UsbAddress::parse("1.4"), Some(UsbAddress::Address { bus: 1, address: 4 }));
+    assert_eq!(UsbAddress::parse("0925.3881"), Some(UsbAddress::Id { vendor: 0x0925, product: 0x3881 }));
+    assert_eq!(UsbAddress::parse("3.300"), None);
+    assert_eq!(UsbAddress::parse("1-1.4"), None);
+    assert_eq!(UsbAddress::parse("/dev/ttyUSB0"), None);
+}
@@ -0,0 +1,141 @@
+use glib_sys::{self, GVariant};
+use num_rational::Ratio;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::ptr;
+use {variant_type, ConfigDataType, Mq, MqFlag};
+
+// A config value of any of the types libsigrok uses, for code that handles keys
+// generically (snapshots, UIs, ...).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    Bool(bool),
+    U64(u64),
+    F64(f64),
+    I32(i32),
+    String(String),
+    U64Range(u64, u64),
+    F64Range(f64, f64),
+    Rational(Ratio<u64>),
+    Mq(Mq, Vec<MqFlag>),
+}
+
+impl ConfigValue {
+    // Decodes by GVariant type. "(tt)" is both a rational and a uint64 range in
+    // libsigrok, so the key's data type decides between the two.
+    pub(crate) unsafe fn from_variant(gvar: *mut GVariant, data_type: Option<ConfigDataType>) -> Option<ConfigValue> {
+        let child = |i: usize| glib_sys::g_variant_get_child_value(gvar, i);
+        let value = match &variant_type(gvar)[..] {
+            "b" => ConfigValue::Bool(glib_sys::g_variant_get_boolean(gvar) != 0),
+            "t" => ConfigValue::U64(glib_sys::g_variant_get_uint64(gvar)),
+            "d" => ConfigValue::F64(glib_sys::g_variant_get_double(gvar)),
+            "i" => ConfigValue::I32(glib_sys::g_variant_get_int32(gvar)),
+            "s" => ConfigValue::String(CStr::from_ptr(glib_sys::g_variant_get_string(gvar, ptr::null_mut())).to_string_lossy().into_owned()),
+            "(tt)" => {
+                let (a, b) = (child(0), child(1));
+                let (p, q) = (glib_sys::g_variant_get_uint64(a), glib_sys::g_variant_get_uint64(b));
+                glib_sys::g_variant_unref(a);
+                glib_sys::g_variant_unref(b);
+                if data_type == Some(ConfigDataType::UInt64Range) {
+                    ConfigValue::U64Range(p, q)
+                } else {
+                    ConfigValue::Rational(Ratio::new_raw(p, q))
+                }
+            }
+            "(dd)" => {
+                let (a, b) = (child(0), child(1));
+                let value = ConfigValue::F64Range(glib_sys::g_variant_get_double(a), glib_sys::g_variant_get_double(b));
+                glib_sys::g_variant_unref(a);
+                glib_sys::g_variant_unref(b);
+                value
+            }
+            "(ut)" => {
+                let (a, b) = (child(0), child(1));
+                let mq = Mq::from_raw(glib_sys::g_variant_get_uint32(a));
+                let flags = MqFlag::from_bits(glib_sys::g_variant_get_uint64(b));
+                glib_sys::g_variant_unref(a);
+                glib_sys::g_variant_unref(b);
+                ConfigValue::Mq(mq?, flags)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // Returns a new floating reference.
+    pub(crate) unsafe fn to_variant(&self) -> *mut GVariant {
+        let tuple = |mut children: [*mut GVariant; 2]| glib_sys::g_variant_new_tuple(children.as_mut_ptr(), children.len());
+        match self {
+            &ConfigValue::Bool(value) => glib_sys::g_variant_new_boolean(value as glib_sys::gboolean),
+            &ConfigValue::U64(value) => glib_sys::g_variant_new_uint64(value),
+            &ConfigValue::F64(value) => glib_sys::g_variant_new_double(value),
+            &ConfigValue::I32(value) => glib_sys::g_variant_new_int32(value),
+            &ConfigValue::String(ref value) => {
+                glib_sys::g_variant_new_string(CString::new(value.as_bytes()).unwrap().as_ptr())
+            }
+            &ConfigValue::U64Range(low, high) => {
+                tuple([glib_sys::g_variant_new_uint64(low), glib_sys::g_variant_new_uint64(high)])
+            }
+            &ConfigValue::F64Range(low, high) => {
+                tuple([glib_sys::g_variant_new_double(low), glib_sys::g_variant_new_double(high)])
+            }
+            &ConfigValue::Rational(ref value) => {
+                tuple([glib_sys::g_variant_new_uint64(*value.numer()), glib_sys::g_variant_new_uint64(*value.denom())])
+            }
+            &ConfigValue::Mq(mq, ref flags) => {
+                let flags = flags.iter().fold(0u64, |acc, x| acc | x.to_raw() as u64);
+                tuple([glib_sys::g_variant_new_uint32(mq.to_raw()), glib_sys::g_variant_new_uint64(flags)])
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a str> for ConfigValue {
+    fn from(value: &'a str) -> ConfigValue {
+        ConfigValue::String(value.to_string())
+    }
+}
+
+macro_rules! config_value_conversions {
+    ($($variant:ident($ty:ty),)*) => {
+        $(
+            impl From<$ty> for ConfigValue {
+                fn from(value: $ty) -> ConfigValue {
+                    ConfigValue::$variant(value)
+                }
+            }
+
+            impl TryFrom<ConfigValue> for $ty {
+                type Error = io::Error;
+
+                fn try_from(value: ConfigValue) -> io::Result<$ty> {
+                    match value {
+                        ConfigValue::$variant(value) => Ok(value),
+                        other => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                    format!("Expected {} config value, got {:?}", stringify!($variant), other))),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+config_value_conversions! {
+    Bool(bool),
+    U64(u64),
+    F64(f64),
+    I32(i32),
+    String(String),
+    Rational(Ratio<u64>),
+}
+
+#[test]
+fn it_converts_config_values() {
+    assert_eq!(ConfigValue::from(true), ConfigValue::Bool(true));
+    assert_eq!(ConfigValue::from("Sine"), ConfigValue::String("Sine".to_string()));
+    assert_eq!(u64::try_from(ConfigValue::U64(10)).unwrap(), 10);
+    assert!(u64::try_from(ConfigValue::F64(1.0)).is_err());
+    assert_eq!(Ratio::<u64>::try_from(ConfigValue::from(Ratio::new_raw(1, 1000))).unwrap(), Ratio::new_raw(1, 1000));
+}
@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use Analog;
+
+// Sample encoding of a WAV file. I16 maps `full_scale` (in the packet's unit)
+// to the largest 16-bit value and clips anything beyond it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WavFormat {
+    F32,
+    I16 { full_scale: f32 },
+}
+
+impl WavFormat {
+    fn bytes_per_sample(&self) -> u16 {
+        match *self {
+            WavFormat::F32 => 4,
+            WavFormat::I16 { .. } => 2,
+        }
+    }
+
+    fn format_tag(&self) -> u16 {
+        match *self {
+            WavFormat::F32 => 3,
+            WavFormat::I16 { .. } => 1,
+        }
+    }
+}
+
+// Writes decoded analog samples to a WAV file. Sizes in the header are filled
+// in by finish(), so a writer that's dropped without it leaves a file most
+// players will reject.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    channels: u16,
+    format: WavFormat,
+    data_len: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, channels: u16, samplerate: u32, format: WavFormat) -> io::Result<WavWriter<BufWriter<File>>> {
+        WavWriter::new(BufWriter::new(File::create(path)?), channels, samplerate, format)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, channels: u16, samplerate: u32, format: WavFormat) -> io::Result<WavWriter<W>> {
+        if channels == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "WAV file needs at least one channel"));
+        }
+        let block_align = channels * format.bytes_per_sample();
+        out.write_all(b"RIFF")?;
+        out.write_all(&le32(0))?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&le32(16))?;
+        out.write_all(&le16(format.format_tag()))?;
+        out.write_all(&le16(channels))?;
+        out.write_all(&le32(samplerate))?;
+        out.write_all(&le32(samplerate.saturating_mul(block_align as u32)))?;
+        out.write_all(&le16(block_align))?;
+        out.write_all(&le16(format.bytes_per_sample() * 8))?;
+        out.write_all(b"data")?;
+        out.write_all(&le32(0))?;
+        Ok(WavWriter {
+            out,
+            channels,
+            format,
+            data_len: 0,
+        })
+    }
+
+    // Appends a packet's values. The packet must carry one value per sample for
+    // each of the file's channels.
+    pub fn write_analog(&mut self, analog: &Analog) -> io::Result<()> {
+        if analog.channels.len() != self.channels as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Packet has {} channels, WAV file has {}", analog.channels.len(), self.channels)));
+        }
+        self.write_frames(&analog.to_float()?)
+    }
+
+    // Appends interleaved values: one per channel for each sample.
+    pub fn write_frames(&mut self, values: &[f32]) -> io::Result<()> {
+        if values.len() % self.channels as usize != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Values don't fill a whole number of frames"));
+        }
+        let len = values.len() as u64 * self.format.bytes_per_sample() as u64;
+        // RIFF sizes are 32-bit, including the 36 header bytes after the RIFF size.
+        if self.data_len as u64 + len > (u32::max_value() - 36) as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "WAV file would exceed 4 GiB"));
+        }
+        for &value in values {
+            match self.format {
+                WavFormat::F32 => self.out.write_all(&le32(value.to_bits()))?,
+                WavFormat::I16 { full_scale } => self.out.write_all(&le16(to_i16(value, full_scale) as u16))?,
+            }
+        }
+        self.data_len += len as u32;
+        Ok(())
+    }
+
+    // Fills in the header sizes and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&le32(36 + self.data_len))?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&le32(self.data_len))?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn to_i16(value: f32, full_scale: f32) -> i16 {
+    let scaled = value / full_scale * i16::max_value() as f32;
+    if scaled.is_nan() {
+        0
+    } else {
+        scaled.round().max(i16::min_value() as f32).min(i16::max_value() as f32) as i16
+    }
+}
+
+fn le16(value: u16) -> [u8; 2] {
+    [value as u8, (value >> 8) as u8]
+}
+
+fn le32(value: u32) -> [u8; 4] {
+    [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]
+}
+
+#[test]
+fn it_writes_wav_files() {
+    use std::io::Cursor;
+
+    let mut wav = WavWriter::new(Cursor::new(vec![]), 2, 8000, WavFormat::I16 { full_scale: 2.0 }).unwrap();
+    wav.write_frames(&[0.0, 1.0, -2.0, 4.0]).unwrap();
+    assert!(wav.write_frames(&[0.0]).is_err());
+    let data = wav.finish().unwrap().into_inner();
+
+    assert_eq!(data.len(), 44 + 8);
+    assert_eq!(&data[0..4], b"RIFF");
+    assert_eq!(&data[4..8], &le32(36 + 8));
+    assert_eq!(&data[22..24], &le16(2));
+    assert_eq!(&data[24..28], &le32(8000));
+    assert_eq!(&data[28..32], &le32(32000));
+    assert_eq!(&data[40..44], &le32(8));
+    assert_eq!(&data[44..], &[0, 0, 0x00, 0x40, 0x01, 0x80, 0xff, 0x7f]);
+}